@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::access_control::members::{ActionType, Member};
+use crate::access_control::rules::RulesList;
+use crate::common::StorageKey;
+use crate::community::CommunityHandle;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::collections::UnorderedMap;
+use near_sdk::{env, near, require, AccountId, Timestamp};
+
+pub type PendingActionId = u64;
+
+/// One week.
+const DEFAULT_APPROVAL_WINDOW: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveActionKind {
+    DeleteCommunity,
+    SetRestrictedRules,
+    RemoveMember,
+    SetFeaturedCommunities,
+}
+
+/// A sensitive action with the arguments it will be executed with.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SensitiveAction {
+    DeleteCommunity { handle: CommunityHandle },
+    SetRestrictedRules { rules: RulesList },
+    RemoveMember { member: Member },
+    SetFeaturedCommunities { handles: Vec<CommunityHandle> },
+}
+
+impl SensitiveAction {
+    pub fn kind(&self) -> SensitiveActionKind {
+        match self {
+            SensitiveAction::DeleteCommunity { .. } => SensitiveActionKind::DeleteCommunity,
+            SensitiveAction::SetRestrictedRules { .. } => SensitiveActionKind::SetRestrictedRules,
+            SensitiveAction::RemoveMember { .. } => SensitiveActionKind::RemoveMember,
+            SensitiveAction::SetFeaturedCommunities { .. } => {
+                SensitiveActionKind::SetFeaturedCommunities
+            }
+        }
+    }
+
+    /// The action type the direct method checks. Actions without one are reserved to moderators
+    /// and the owner.
+    fn action_type(&self) -> Option<ActionType> {
+        match self {
+            SensitiveAction::DeleteCommunity { .. }
+            | SensitiveAction::SetFeaturedCommunities { .. } => {
+                Some(ActionType::ModerateCommunities)
+            }
+            SensitiveAction::SetRestrictedRules { .. } | SensitiveAction::RemoveMember { .. } => {
+                None
+            }
+        }
+    }
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApprovalPolicy {
+    /// How many distinct accounts that could call the action directly have to approve it. Kinds
+    /// that are not listed, or have a threshold of 1 or less, are executed right away.
+    pub thresholds: HashMap<SensitiveActionKind, u32>,
+    /// Nanoseconds after the proposal during which the approvals have to be collected.
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub window: u64,
+}
+
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        Self { thresholds: HashMap::new(), window: DEFAULT_APPROVAL_WINDOW }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn threshold(&self, kind: SensitiveActionKind) -> u32 {
+        self.thresholds.get(&kind).copied().unwrap_or(1).max(1)
+    }
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingAction {
+    pub id: PendingActionId,
+    pub action: SensitiveAction,
+    pub proposer: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub created_at: Timestamp,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub expires_at: Timestamp,
+    pub approvals: HashSet<AccountId>,
+}
+
+impl PendingAction {
+    pub fn is_expired(&self) -> bool {
+        env::block_timestamp() > self.expires_at
+    }
+}
+
+#[near]
+pub struct SensitiveActions {
+    pub policy: ApprovalPolicy,
+    pub next_id: PendingActionId,
+    pub pending: UnorderedMap<PendingActionId, PendingAction>,
+}
+
+impl Default for SensitiveActions {
+    fn default() -> Self {
+        Self {
+            policy: ApprovalPolicy::default(),
+            next_id: 0,
+            pending: UnorderedMap::new(StorageKey::PendingSensitiveActions),
+        }
+    }
+}
+
+impl Contract {
+    /// Sensitive actions called directly only run when they don't need approvals. The contract
+    /// account and the owner are never asked for approvals.
+    pub(crate) fn assert_no_approval_required(&self, kind: SensitiveActionKind) {
+        let threshold = self.sensitive_actions.policy.threshold(kind);
+        require!(
+            threshold <= 1 || self.is_owner(&env::predecessor_account_id()),
+            format!(
+                "This action needs the approval of {} accounts allowed to call it, use propose_sensitive_action",
+                threshold
+            )
+        );
+    }
+
+    /// Accounts can propose and approve the actions they could call directly, e.g. accounts
+    /// granted moderate-communities can approve deleting a community without being moderators.
+    fn can_approve_sensitive_action(
+        &self,
+        account_id: &AccountId,
+        action: &SensitiveAction,
+    ) -> bool {
+        match action.action_type() {
+            Some(action_type) => self.is_allowed_to(account_id, action_type, vec![]),
+            None => self.has_moderator(account_id.clone()) || self.is_owner(account_id),
+        }
+    }
+
+    fn assert_can_approve_sensitive_action(&self, action: &SensitiveAction) {
+        require!(
+            self.can_approve_sensitive_action(&env::predecessor_account_id(), action),
+            "The account is not allowed to propose or approve this action"
+        );
+    }
+
+    fn execute_sensitive_action(&mut self, action: SensitiveAction) {
+        match action {
+            SensitiveAction::DeleteCommunity { handle } => {
+                // near-sdk schedules a promise when it is dropped. The result of the approval
+                // does not depend on the community accounts being destroyed, so the promise is
+                // not returned.
+                let destroy_accounts = self.delete_community_unchecked(handle);
+                drop(destroy_accounts);
+            }
+            SensitiveAction::SetRestrictedRules { rules } => {
+                self.set_restricted_rules_unchecked(rules);
+            }
+            SensitiveAction::RemoveMember { member } => {
+                self.remove_member_unchecked(&member);
+            }
+            SensitiveAction::SetFeaturedCommunities { handles } => {
+                self.set_featured_communities_unchecked(handles);
+            }
+        }
+    }
+
+    /// Runs the action once enough accounts that are still allowed to approve it did.
+    fn try_execute_pending_action(&mut self, mut pending_action: PendingAction) -> bool {
+        pending_action.approvals.retain(|account_id| {
+            self.can_approve_sensitive_action(account_id, &pending_action.action)
+        });
+        let threshold = self.sensitive_actions.policy.threshold(pending_action.action.kind());
+        if (pending_action.approvals.len() as u32) < threshold {
+            self.sensitive_actions.pending.insert(&pending_action.id, &pending_action);
+            return false;
+        }
+        self.sensitive_actions.pending.remove(&pending_action.id);
+        self.execute_sensitive_action(pending_action.action);
+        true
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_sensitive_action_policy(&self) -> ApprovalPolicy {
+        self.sensitive_actions.policy.clone()
+    }
+
+    /// Only the owner can change the policy, so that the accounts bound by the thresholds can't
+    /// lower them.
+    pub fn set_sensitive_action_policy(&mut self, policy: ApprovalPolicy) {
+        require!(
+            self.is_owner(&env::predecessor_account_id()),
+            "Only the owner can set the sensitive action policy"
+        );
+        require!(policy.window > 0, "The approval window must be positive");
+        self.sensitive_actions.policy = policy;
+    }
+
+    pub fn get_pending_sensitive_actions(&self) -> Vec<PendingAction> {
+        let mut res: Vec<_> = self.sensitive_actions.pending.values().collect();
+        res.sort_by_key(|pending_action| pending_action.id);
+        res
+    }
+
+    /// Records the action with the approval of the proposer. The action runs right away when that
+    /// is enough, in that case `None` is returned.
+    pub fn propose_sensitive_action(&mut self, action: SensitiveAction) -> Option<PendingActionId> {
+        self.assert_can_approve_sensitive_action(&action);
+        let id = self.sensitive_actions.next_id;
+        self.sensitive_actions.next_id += 1;
+        let pending_action = PendingAction {
+            id,
+            action,
+            proposer: env::predecessor_account_id(),
+            created_at: env::block_timestamp(),
+            expires_at: env::block_timestamp().saturating_add(self.sensitive_actions.policy.window),
+            approvals: HashSet::from([env::predecessor_account_id()]),
+        };
+        if self.try_execute_pending_action(pending_action) {
+            None
+        } else {
+            Some(id)
+        }
+    }
+
+    /// Returns whether the action was executed by this approval.
+    pub fn approve_sensitive_action(&mut self, id: PendingActionId) -> bool {
+        let mut pending_action = self
+            .sensitive_actions
+            .pending
+            .get(&id)
+            .unwrap_or_else(|| panic!("Pending action {} not found", id));
+        self.assert_can_approve_sensitive_action(&pending_action.action);
+        require!(!pending_action.is_expired(), format!("Pending action {} has expired", id));
+        require!(
+            pending_action.approvals.insert(env::predecessor_account_id()),
+            "The account already approved this action"
+        );
+        self.try_execute_pending_action(pending_action)
+    }
+
+    /// The proposer and the owner, including the contract account itself, can cancel a pending
+    /// action, and anyone can clean up an expired one.
+    pub fn cancel_sensitive_action(&mut self, id: PendingActionId) {
+        let pending_action = self
+            .sensitive_actions
+            .pending
+            .get(&id)
+            .unwrap_or_else(|| panic!("Pending action {} not found", id));
+        let account_id = env::predecessor_account_id();
+        require!(
+            pending_action.is_expired()
+                || account_id == pending_action.proposer
+                || self.is_owner(&account_id),
+            "Only the proposer and the owner can cancel a pending action"
+        );
+        self.sensitive_actions.pending.remove(&id);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::access_control::members::{Member, VersionedMemberMetadata};
+use crate::access_control::rules::RulesList;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, AccountId, BlockHeight, Timestamp};
+
+const DEFAULT_AUDIT_LOG_PAGE_LIMIT: u64 = 50;
+
+/// What an access control mutation changed, with the affected entries before and after it.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AccessControlChange {
+    AddMember {
+        member: Member,
+        after: VersionedMemberMetadata,
+    },
+    RemoveMember {
+        member: Member,
+        before: VersionedMemberMetadata,
+    },
+    EditMember {
+        member: Member,
+        before: VersionedMemberMetadata,
+        after: VersionedMemberMetadata,
+    },
+    /// `before` only has the rules that were already restricted.
+    SetRestrictedRules {
+        before: RulesList,
+        after: RulesList,
+    },
+    /// `before` only has the rules that were restricted.
+    UnsetRestrictedRules {
+        before: RulesList,
+    },
+    /// A community team moved along with its community to a new handle.
+    RenameMember {
+        member: Member,
+        new_member: Member,
+    },
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessControlAuditEntry {
+    pub actor: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub block_height: BlockHeight,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub timestamp: Timestamp,
+    pub change: AccessControlChange,
+}
+
+impl Contract {
+    pub(crate) fn add_access_control_audit_entry(&mut self, change: AccessControlChange) {
+        self.access_control_audit_log.push(&AccessControlAuditEntry {
+            actor: env::predecessor_account_id(),
+            block_height: env::block_height(),
+            timestamp: env::block_timestamp(),
+            change,
+        });
+    }
+}
+
+#[near]
+impl Contract {
+    /// Access control mutations in the order they were made, oldest first.
+    pub fn get_access_control_audit_log(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<AccessControlAuditEntry> {
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_AUDIT_LOG_PAGE_LIMIT);
+        (from_index..self.access_control_audit_log.len().min(from_index.saturating_add(limit)))
+            .filter_map(|index| self.access_control_audit_log.get(index))
+            .collect()
+    }
+}
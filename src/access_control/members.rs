@@ -1,5 +1,6 @@
 use crate::access_control::rules::Rule;
-use near_sdk::{near, AccountId};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, AccountId};
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 
@@ -52,6 +53,50 @@ pub enum ActionType {
     EditPost,
     /// Can add/remove labels that fall under these rules.
     UseLabels,
+    /// Can create and edit RFPs.
+    WriteRfp,
+    /// Can set the global labels and rename, merge or deprecate them.
+    ManageLabels,
+    /// Can set the allowed proposal categories.
+    ManageCategories,
+    /// Can create, edit and delete add-ons.
+    ManageAddons,
+    /// Can configure and delete any community and set the featured communities.
+    ModerateCommunities,
+    /// Can change the timeline of proposals that have these labels, bypassing the proposal
+    /// workflow.
+    ChangeProposalTimeline,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Default, Debug, Eq, PartialEq)]
+pub struct MemberMetadataV1 {
+    pub description: String,
+    pub permissions: HashMap<Rule, HashSet<ActionType>>,
+    pub children: HashSet<Member>,
+    pub parents: HashSet<Member>,
+    /// Block timestamp in nanoseconds after which the member loses its permissions and stops
+    /// passing the permissions of its parents on.
+    #[serde(default)]
+    pub valid_until: Option<U64>,
+}
+
+impl MemberMetadataV1 {
+    pub fn is_expired(&self) -> bool {
+        self.valid_until.map_or(false, |valid_until| env::block_timestamp() > valid_until.0)
+    }
+}
+
+impl From<MemberMetadata> for MemberMetadataV1 {
+    fn from(m: MemberMetadata) -> Self {
+        MemberMetadataV1 {
+            description: m.description,
+            permissions: m.permissions,
+            children: m.children,
+            parents: m.parents,
+            valid_until: None,
+        }
+    }
 }
 
 #[near(serializers=[borsh, json])]
@@ -59,12 +104,36 @@ pub enum ActionType {
 #[serde(tag = "member_metadata_version")]
 pub enum VersionedMemberMetadata {
     V0(MemberMetadata),
+    V1(MemberMetadataV1),
 }
 
 impl VersionedMemberMetadata {
-    pub fn last_version(&self) -> MemberMetadata {
+    pub fn last_version(&self) -> MemberMetadataV1 {
+        match self {
+            VersionedMemberMetadata::V0(v0) => v0.clone().into(),
+            VersionedMemberMetadata::V1(v1) => v1.clone(),
+        }
+    }
+
+    /// Updates the permissions keeping the version of the metadata.
+    pub fn update_permissions(
+        &mut self,
+        update: impl FnOnce(&mut HashMap<Rule, HashSet<ActionType>>),
+    ) {
+        match self {
+            VersionedMemberMetadata::V0(v0) => update(&mut v0.permissions),
+            VersionedMemberMetadata::V1(v1) => update(&mut v1.permissions),
+        }
+    }
+
+    /// Updates the children and the parents keeping the version of the metadata.
+    fn update_relations(
+        &mut self,
+        update: impl FnOnce(&mut HashSet<Member>, &mut HashSet<Member>),
+    ) {
         match self {
-            VersionedMemberMetadata::V0(v0) => v0.clone(),
+            VersionedMemberMetadata::V0(v0) => update(&mut v0.children, &mut v0.parents),
+            VersionedMemberMetadata::V1(v1) => update(&mut v1.children, &mut v1.parents),
         }
     }
 }
@@ -75,6 +144,24 @@ impl From<MemberMetadata> for VersionedMemberMetadata {
     }
 }
 
+impl From<MemberMetadataV1> for VersionedMemberMetadata {
+    fn from(m: MemberMetadataV1) -> Self {
+        VersionedMemberMetadata::V1(m)
+    }
+}
+
+/// A permission declared on a member that the account inherits, and whether its rule matched
+/// the labels.
+#[near(serializers=[json])]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PermissionGrant {
+    pub action: ActionType,
+    pub rule: Rule,
+    /// From the account itself up to the member that declares the permission.
+    pub path: Vec<Member>,
+    pub granted: bool,
+}
+
 #[near(serializers=[borsh, json])]
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub struct MembersList {
@@ -104,18 +191,25 @@ impl MembersList {
             return HashSet::new();
         }
 
+        let mut visited = HashSet::new();
         let mut stack = HashSet::new();
         stack.insert(member_account);
 
         let mut permissions = HashSet::new();
         while let Some(member) = stack.iter().next().cloned() {
             stack.remove(&member);
+            if !visited.insert(member.clone()) {
+                continue;
+            }
 
             let metadata = self
                 .members
                 .get(&member)
                 .unwrap_or_else(|| panic!("Metadata not found for {:#?}", member))
                 .last_version();
+            if metadata.is_expired() {
+                continue;
+            }
 
             for (member_rule, member_permissions) in metadata.permissions {
                 if member_rule.applies_to_any(&labels) {
@@ -128,22 +222,99 @@ impl MembersList {
         permissions
     }
 
+    /// Same walk as `check_permissions`, but lists every permission the account inherits with
+    /// the path it was inherited through, including the ones whose rule does not match the labels.
+    pub fn explain_permissions(
+        &self,
+        account: AccountId,
+        labels: &[String],
+    ) -> Vec<PermissionGrant> {
+        let member_account = Member::Account(account);
+        if !self.members.contains_key(&member_account) {
+            return vec![];
+        }
+
+        let mut visited = HashSet::new();
+        let mut stack = vec![vec![member_account]];
+        let mut grants = vec![];
+        while let Some(path) = stack.pop() {
+            let member = path.last().unwrap().clone();
+            if !visited.insert(member.clone()) {
+                continue;
+            }
+
+            let metadata = self
+                .members
+                .get(&member)
+                .unwrap_or_else(|| panic!("Metadata not found for {:#?}", member))
+                .last_version();
+            if metadata.is_expired() {
+                continue;
+            }
+
+            for (member_rule, member_permissions) in &metadata.permissions {
+                let granted = member_rule.applies_to_any(labels);
+                for action in member_permissions {
+                    grants.push(PermissionGrant {
+                        action: action.clone(),
+                        rule: member_rule.clone(),
+                        path: path.clone(),
+                        granted,
+                    });
+                }
+            }
+
+            for parent in metadata.parents {
+                let mut parent_path = path.clone();
+                parent_path.push(parent);
+                stack.push(parent_path);
+            }
+        }
+        grants.sort_by(|a, b| {
+            a.action.cmp(&b.action).then(a.path.cmp(&b.path)).then(a.rule.cmp(&b.rule))
+        });
+        grants
+    }
+
+    /// Checks that the new member only references existing members and does not close a cycle.
+    fn assert_valid_relations(&self, member: &Member, metadata: &MemberMetadataV1) {
+        for child in &metadata.children {
+            assert!(child != member, "Member cannot be its own child");
+            assert!(
+                self.members.contains_key(child),
+                "Member declares a child {:#?} that does not exist",
+                child
+            );
+        }
+        for parent in &metadata.parents {
+            assert!(parent != member, "Member cannot be its own parent");
+            assert!(
+                self.members.contains_key(parent),
+                "Member declares a parent {:#?} that does not exist",
+                parent
+            );
+            for child in &metadata.children {
+                assert!(
+                    parent != child && !self.is_descendant_of(parent, child, false),
+                    "Member {:#?} would be both an ancestor and a descendant of {:#?}",
+                    member,
+                    child
+                );
+            }
+        }
+    }
+
     pub fn add_member(&mut self, member: Member, metadata: VersionedMemberMetadata) {
-        assert!(
-            self.members.insert(member.clone(), metadata.clone()).is_none(),
-            "Member already exists"
-        );
+        assert!(!self.members.contains_key(&member), "Member already exists");
+        self.assert_valid_relations(&member, &metadata.last_version());
+        self.members.insert(member.clone(), metadata.clone());
 
         // Update child members that this member is a parent of.
         for child in &metadata.last_version().children {
             match self.members.entry(child.clone()) {
-                Entry::Occupied(mut occ) => {
-                    let MemberMetadata { description, children, mut parents, permissions } =
-                        occ.get().last_version();
-                    assert!(parents.insert(member.clone()), "Child already had this parent");
-                    let new_child = MemberMetadata { description, children, parents, permissions };
-                    occ.insert(new_child.into());
-                }
+                Entry::Occupied(mut occ) => occ.get_mut().update_relations(|_, parents| {
+                    assert!(parents.insert(member.clone()), "Child already had this parent")
+                }),
                 Entry::Vacant(_) => {
                     panic!("Member declares a child {:#?} that does not exist", child)
                 }
@@ -153,13 +324,9 @@ impl MembersList {
         // Update parent members that this member is now a child of.
         for parent in &metadata.last_version().parents {
             match self.members.entry(parent.clone()) {
-                Entry::Occupied(mut occ) => {
-                    let MemberMetadata { description, mut children, parents, permissions } =
-                        occ.get().last_version();
-                    assert!(children.insert(member.clone()), "Parent already had this child");
-                    let new_parent = MemberMetadata { description, children, parents, permissions };
-                    occ.insert(new_parent.into());
-                }
+                Entry::Occupied(mut occ) => occ.get_mut().update_relations(|children, _| {
+                    assert!(children.insert(member.clone()), "Parent already had this child")
+                }),
                 Entry::Vacant(_) => {
                     panic!("Member declares a parent {:#?} that does not exist", parent)
                 }
@@ -173,13 +340,9 @@ impl MembersList {
         // Update child members that this member is not a parent of anymore.
         for child in &metadata.last_version().children {
             match self.members.entry(child.clone()) {
-                Entry::Occupied(mut occ) => {
-                    let MemberMetadata { description, children, mut parents, permissions } =
-                        occ.get().last_version();
-                    assert!(parents.remove(member), "Child did not have this parent.");
-                    let new_child = MemberMetadata { description, children, parents, permissions };
-                    occ.insert(new_child.into());
-                }
+                Entry::Occupied(mut occ) => occ.get_mut().update_relations(|_, parents| {
+                    assert!(parents.remove(member), "Child did not have this parent.")
+                }),
                 Entry::Vacant(_) => {
                     panic!("Member declares a child {:#?} that does not exist", child)
                 }
@@ -189,13 +352,9 @@ impl MembersList {
         // Update parent members that this member is not a child of anymore.
         for parent in &metadata.last_version().parents {
             match self.members.entry(parent.clone()) {
-                Entry::Occupied(mut occ) => {
-                    let MemberMetadata { description, mut children, parents, permissions } =
-                        occ.get().last_version();
-                    assert!(children.remove(member), "Parent did not have this child.");
-                    let new_parent = MemberMetadata { description, children, parents, permissions };
-                    occ.insert(new_parent.into());
-                }
+                Entry::Occupied(mut occ) => occ.get_mut().update_relations(|children, _| {
+                    assert!(children.remove(member), "Parent did not have this child.")
+                }),
                 Entry::Vacant(_) => {
                     panic!("Member declares a parent {:#?} that does not exist", parent)
                 }
@@ -203,26 +362,140 @@ impl MembersList {
         }
     }
 
+    /// Moves the member to a new name, keeping its metadata and the relations that point to it.
+    pub fn rename_member(&mut self, member: &Member, new_member: Member) {
+        assert!(!self.members.contains_key(&new_member), "Member already exists");
+        let metadata = self.members.remove(member).expect("Member does not exist");
+        let last_version = metadata.last_version();
+        for related in last_version.children.iter().chain(last_version.parents.iter()) {
+            if let Some(related_metadata) = self.members.get_mut(related) {
+                related_metadata.update_relations(|children, parents| {
+                    if children.remove(member) {
+                        children.insert(new_member.clone());
+                    }
+                    if parents.remove(member) {
+                        parents.insert(new_member.clone());
+                    }
+                });
+            }
+        }
+        self.members.insert(new_member, metadata);
+    }
+
+    /// The edit is applied to a copy of the list, so an invalid edit leaves the list untouched.
     pub fn edit_member(&mut self, member: Member, metadata: VersionedMemberMetadata) {
-        self.remove_member(&member);
-        self.add_member(member, metadata);
+        let mut members = self.clone();
+        members.remove_member(&member);
+        members.add_member(member, metadata);
+        *self = members;
     }
 
-    pub fn get_moderators(&self) -> HashSet<Member> {
+    /// Lists dangling references, relations that are only declared on one side and cycles.
+    pub fn find_inconsistencies(&self) -> Vec<String> {
+        let name = |member: &Member| -> String { member.clone().into() };
+        let mut issues = vec![];
+        for (member, metadata) in &self.members {
+            let metadata = metadata.last_version();
+            for child in &metadata.children {
+                match self.members.get(child) {
+                    None => issues.push(format!(
+                        "{} declares a child {} that does not exist",
+                        name(member),
+                        name(child)
+                    )),
+                    Some(child_metadata)
+                        if !child_metadata.last_version().parents.contains(member) =>
+                    {
+                        issues.push(format!(
+                            "{} declares a child {} that does not declare it as a parent",
+                            name(member),
+                            name(child)
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for parent in &metadata.parents {
+                match self.members.get(parent) {
+                    None => issues.push(format!(
+                        "{} declares a parent {} that does not exist",
+                        name(member),
+                        name(parent)
+                    )),
+                    Some(parent_metadata)
+                        if !parent_metadata.last_version().children.contains(member) =>
+                    {
+                        issues.push(format!(
+                            "{} declares a parent {} that does not declare it as a child",
+                            name(member),
+                            name(parent)
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            if self.is_descendant_of(member, member, false) {
+                issues.push(format!("{} is its own ancestor", name(member)));
+            }
+        }
+        issues.sort();
+        issues
+    }
+
+    /// Whether the member belongs to the team, either directly or through one of its parents.
+    /// Expired members don't belong to any team.
+    pub fn is_member_of(&self, member: &Member, team: &Member) -> bool {
+        !self.is_expired(team) && self.is_descendant_of(member, team, true)
+    }
+
+    fn is_descendant_of(&self, member: &Member, team: &Member, skip_expired: bool) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![member.clone()];
+        while let Some(current) = stack.pop() {
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+            let Some(metadata) = self.members.get(&current) else {
+                continue;
+            };
+            let metadata = metadata.last_version();
+            if skip_expired && metadata.is_expired() {
+                continue;
+            }
+            if metadata.parents.contains(team) {
+                return true;
+            }
+            stack.extend(metadata.parents);
+        }
+        false
+    }
+
+    /// Active children of the moderators team.
+    pub fn get_moderators(&self, moderators_team: &str) -> HashSet<Member> {
         self.members
-            .get(&Member::Team("moderators".to_string()))
+            .get(&Member::Team(moderators_team.to_string()))
             .map(|team| team.last_version().children)
             .unwrap_or(HashSet::new())
+            .into_iter()
+            .filter(|member| !self.is_expired(member))
+            .collect()
+    }
+
+    fn is_expired(&self, member: &Member) -> bool {
+        self.members.get(member).map_or(false, |metadata| metadata.last_version().is_expired())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::access_control::members::{
-        ActionType, Member, MemberMetadata, MembersList, VersionedMemberMetadata,
+        ActionType, Member, MemberMetadata, MemberMetadataV1, MembersList, PermissionGrant,
+        VersionedMemberMetadata,
     };
     use crate::access_control::rules::Rule;
     use near_sdk::serde_json;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
     use std::collections::{HashMap, HashSet};
 
     #[test]
@@ -353,6 +626,33 @@ mod tests {
         assert!(actual.is_empty());
     }
 
+    #[test]
+    fn explain_permissions() {
+        let list = create_list();
+        let path = vec![
+            Member::Account("max.near".parse().unwrap()),
+            Member::Team("moderators".to_string()),
+        ];
+        let grant = |action, rule: &str, granted| PermissionGrant {
+            action,
+            rule: Rule::StartsWith(rule.to_string()),
+            path: path.clone(),
+            granted,
+        };
+        assert_eq!(
+            list.explain_permissions("max.near".parse().unwrap(), &["wg-protocol".to_string()]),
+            vec![
+                grant(ActionType::EditPost, "mnw", false),
+                grant(ActionType::EditPost, "wg-", true),
+                grant(ActionType::UseLabels, "mnw", false),
+                grant(ActionType::UseLabels, "wg-", true),
+            ]
+        );
+        assert!(list
+            .explain_permissions("random.near".parse().unwrap(), &["wg-protocol".to_string()])
+            .is_empty());
+    }
+
     #[test]
     fn check_permissions_rules_any() {
         let mut list = create_list();
@@ -406,4 +706,128 @@ mod tests {
         list.remove_member(&Member::Account("bob.near".parse().unwrap()));
         assert_eq!(list, create_list());
     }
+
+    #[test]
+    fn expired_members() {
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        let mut list = create_list();
+        let member = Member::Account("max.near".parse().unwrap());
+        let metadata = list.members[&member].last_version();
+        list.edit_member(
+            member.clone(),
+            MemberMetadataV1 { valid_until: Some(200.into()), ..metadata.clone() }.into(),
+        );
+        assert!(list.get_moderators("moderators").contains(&member));
+        assert!(list.is_member_of(&member, &Member::Team("moderators".to_string())));
+
+        list.edit_member(
+            member.clone(),
+            MemberMetadataV1 { valid_until: Some(50.into()), ..metadata }.into(),
+        );
+        assert!(!list.get_moderators("moderators").contains(&member));
+        assert!(!list.is_member_of(&member, &Member::Team("moderators".to_string())));
+        assert!(list
+            .check_permissions("max.near".parse().unwrap(), vec!["wg-protocol".to_string()])
+            .is_empty());
+        assert!(list.find_inconsistencies().is_empty());
+    }
+
+    #[test]
+    fn member_metadata_v1_deserialization() {
+        let metadata: VersionedMemberMetadata = serde_json::from_value(serde_json::json!({
+            "member_metadata_version": "V1",
+            "description": "",
+            "permissions": {},
+            "children": [],
+            "parents": ["team:moderators"],
+            "valid_until": "1000"
+        }))
+        .unwrap();
+        assert_eq!(metadata.last_version().valid_until, Some(1000.into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "would be both an ancestor and a descendant")]
+    fn add_member_with_cycle() {
+        let mut list = create_list();
+        list.add_member(
+            Member::Team("leads".to_string()),
+            MemberMetadata {
+                parents: HashSet::from([Member::Account("max.near".parse().unwrap())]),
+                children: HashSet::from([Member::Team("moderators".to_string())]),
+                ..Default::default()
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "that does not exist")]
+    fn edit_member_with_dangling_parent() {
+        let mut list = create_list();
+        list.edit_member(
+            Member::Account("max.near".parse().unwrap()),
+            MemberMetadata {
+                parents: HashSet::from([Member::Team("leads".to_string())]),
+                ..Default::default()
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn edit_member() {
+        let mut list = create_list();
+        let (member, metadata) = moderator_member("max.near");
+        list.edit_member(
+            member.clone(),
+            MemberMetadataV1 { parents: HashSet::new(), ..metadata.last_version() }.into(),
+        );
+        assert!(!list.get_moderators("moderators").contains(&member));
+        assert!(list.find_inconsistencies().is_empty());
+
+        list.edit_member(member.clone(), metadata);
+        assert_eq!(list, create_list());
+    }
+
+    #[test]
+    fn rename_member() {
+        let mut list = create_list();
+        let moderators = Member::Team("moderators".to_string());
+        let stewards = Member::Team("stewards".to_string());
+        list.rename_member(&moderators, stewards.clone());
+
+        assert!(!list.members.contains_key(&moderators));
+        assert!(list.get_moderators("moderators").is_empty());
+        assert_eq!(list.get_moderators("stewards"), create_list().get_moderators("moderators"));
+        assert!(list.find_inconsistencies().is_empty());
+    }
+
+    #[test]
+    fn find_inconsistencies() {
+        let mut list = create_list();
+        assert!(list.find_inconsistencies().is_empty());
+
+        list.members.insert(
+            Member::Team("leads".to_string()),
+            MemberMetadata {
+                parents: HashSet::from([
+                    Member::Team("leads".to_string()),
+                    Member::Team("ghosts".to_string()),
+                ]),
+                children: HashSet::from([Member::Account("ori.near".parse().unwrap())]),
+                ..Default::default()
+            }
+            .into(),
+        );
+        assert_eq!(
+            list.find_inconsistencies(),
+            vec![
+                "team:leads declares a child ori.near that does not declare it as a parent",
+                "team:leads declares a parent team:ghosts that does not exist",
+                "team:leads declares a parent team:leads that does not declare it as a child",
+                "team:leads is its own ancestor",
+            ]
+        );
+    }
 }
@@ -1,12 +1,21 @@
-use crate::access_control::members::{Member, MembersList, VersionedMemberMetadata};
+use crate::access_control::approvals::SensitiveActionKind;
+use crate::access_control::audit::AccessControlChange;
+use crate::access_control::members::{
+    ActionType, Member, MembersList, PermissionGrant, VersionedMemberMetadata,
+};
 use crate::access_control::rules::{Rule, RulesList};
 use crate::*;
 use near_sdk::near;
 use std::collections::{HashMap, HashSet};
 
+pub mod approvals;
+pub mod audit;
 pub mod members;
 pub mod rules;
 
+/// The team whose children are the moderators, until another team is designated.
+pub const DEFAULT_MODERATORS_TEAM: &str = "moderators";
+
 #[near(serializers=[borsh, json])]
 #[derive(Clone, Default)]
 pub struct AccessControl {
@@ -14,6 +23,76 @@ pub struct AccessControl {
     pub members_list: MembersList,
 }
 
+/// Why an account is or is not allowed to use labels and edit posts with them.
+#[near(serializers=[json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PermissionsExplanation {
+    /// Restricted rules that match at least one of the labels.
+    pub restricted_rules: Vec<Rule>,
+    /// The owner and the contract account bypass every check.
+    pub is_owner: bool,
+    pub is_moderator: bool,
+    pub grants: Vec<PermissionGrant>,
+    pub can_use_labels: bool,
+}
+
+/// Why an account is or is not allowed to edit a proposal.
+#[near(serializers=[json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProposalPermissionsExplanation {
+    /// The author of the proposal can always edit it.
+    pub is_author: bool,
+    /// The permissions of the account for the labels of the proposal.
+    pub labels: PermissionsExplanation,
+    pub can_edit_proposal: bool,
+}
+
+impl Contract {
+    /// Only the contract account can leave the contract without moderators. Called after the
+    /// mutation, the panic reverts it.
+    pub(crate) fn assert_moderators_not_locked_out(&self) {
+        let has_moderators = self
+            .access_control
+            .members_list
+            .get_moderators(&self.moderators_team)
+            .iter()
+            .any(|member| matches!(member, Member::Account(_)));
+        require!(
+            has_moderators || self.is_owner(&env::predecessor_account_id()),
+            format!(
+                "The moderators team {} can't be left without moderators",
+                self.moderators_team
+            )
+        );
+    }
+
+    /// Whether the account was granted the action for the labels through its teams.
+    pub(crate) fn is_granted(
+        &self,
+        account_id: &AccountId,
+        action: ActionType,
+        labels: Vec<String>,
+    ) -> bool {
+        self.access_control
+            .members_list
+            .check_permissions(account_id.clone(), labels)
+            .contains(&action)
+    }
+
+    /// Whether the account is the contract itself, a moderator or was granted the action for the
+    /// labels. Actions that are not tied to labels are only granted through the `*` rule.
+    pub(crate) fn is_allowed_to(
+        &self,
+        account_id: &AccountId,
+        action: ActionType,
+        labels: Vec<String>,
+    ) -> bool {
+        self.is_owner(account_id)
+            || self.has_moderator(account_id.clone())
+            || self.is_granted(account_id, action, labels)
+    }
+}
+
 #[near]
 impl Contract {
     pub fn get_access_control_info(&self) -> &AccessControl {
@@ -28,24 +107,127 @@ impl Contract {
         self.access_control.rules_list.find_restricted(&labels)
     }
 
+    /// Explains the result of `is_allowed_to_use_labels`.
+    pub fn explain_permissions(
+        &self,
+        account_id: AccountId,
+        labels: Vec<String>,
+    ) -> PermissionsExplanation {
+        let mut restricted_rules: Vec<_> = self
+            .access_control
+            .rules_list
+            .rules
+            .keys()
+            .filter(|rule| labels.iter().any(|label| rule.applies(label)))
+            .cloned()
+            .collect();
+        restricted_rules.sort();
+
+        PermissionsExplanation {
+            restricted_rules,
+            is_owner: self.is_owner(&account_id),
+            is_moderator: self.has_moderator(account_id.clone()),
+            grants: self
+                .access_control
+                .members_list
+                .explain_permissions(account_id.clone(), &labels),
+            can_use_labels: self.is_allowed_to_use_labels(Some(account_id), labels),
+        }
+    }
+
+    /// Explains the result of `is_allowed_to_edit_proposal` with the labels of the proposal.
+    pub fn explain_proposal_permissions(
+        &self,
+        account_id: AccountId,
+        proposal_id: ProposalId,
+    ) -> ProposalPermissionsExplanation {
+        let proposal: Proposal = self.get_proposal(proposal_id).into();
+        let mut labels: Vec<_> = proposal.snapshot.labels.into_iter().collect();
+        labels.sort();
+        ProposalPermissionsExplanation {
+            is_author: proposal.author_id == account_id,
+            labels: self.explain_permissions(account_id.clone(), labels),
+            can_edit_proposal: self.is_allowed_to_edit_proposal(proposal_id, Some(account_id)),
+        }
+    }
+
+    /// Inconsistencies in the member graph, empty when the access control state is valid.
+    pub fn validate_access_control(&self) -> Vec<String> {
+        self.access_control.members_list.find_inconsistencies()
+    }
+
     pub fn set_restricted_rules(&mut self, rules: RulesList) {
         require!(
             self.has_moderator(env::predecessor_account_id())
-                || env::predecessor_account_id() == env::current_account_id(),
+                || self.is_owner(&env::predecessor_account_id()),
             "Only the admin and moderators can set restricted rules"
         );
+        self.assert_no_approval_required(SensitiveActionKind::SetRestrictedRules);
+        self.set_restricted_rules_unchecked(rules)
+    }
+
+    pub(crate) fn set_restricted_rules_unchecked(&mut self, rules: RulesList) {
+        let before = RulesList {
+            rules: rules
+                .rules
+                .keys()
+                .filter_map(|rule| {
+                    let metadata = self.access_control.rules_list.rules.get(rule)?;
+                    Some((rule.clone(), metadata.clone()))
+                })
+                .collect(),
+        };
+        self.add_access_control_audit_entry(AccessControlChange::SetRestrictedRules {
+            before,
+            after: rules.clone(),
+        });
         self.access_control.rules_list.set_restricted(rules)
     }
 
     pub fn unset_restricted_rules(&mut self, rules: Vec<Rule>) {
         require!(
             self.has_moderator(env::predecessor_account_id())
-                || env::predecessor_account_id() == env::current_account_id(),
+                || self.is_owner(&env::predecessor_account_id()),
             "Only the admin and moderators can unset restricted rules"
         );
+        let before = RulesList {
+            rules: rules
+                .iter()
+                .filter_map(|rule| {
+                    let metadata = self.access_control.rules_list.rules.get(rule)?;
+                    Some((rule.clone(), metadata.clone()))
+                })
+                .collect(),
+        };
+        self.add_access_control_audit_entry(AccessControlChange::UnsetRestrictedRules { before });
         self.access_control.rules_list.unset_restricted(rules)
     }
 
+    pub fn get_moderators_team(&self) -> Member {
+        Member::Team(self.moderators_team.clone())
+    }
+
+    /// Designates another existing team as the moderators team, e.g. after creating a team with
+    /// a new name. The team must have at least one account among its active children. Community
+    /// teams, named `<handle>/<team>`, are managed by the community admins and can't be chosen.
+    pub fn set_moderators_team(&mut self, team: String) {
+        require!(
+            self.has_moderator(env::predecessor_account_id())
+                || self.is_owner(&env::predecessor_account_id()),
+            "Only the admin and moderators can set the moderators team"
+        );
+        require!(
+            !team.contains('/'),
+            format!("Team {} belongs to a community and can't be the moderators team", team)
+        );
+        require!(
+            self.access_control.members_list.members.contains_key(&Member::Team(team.clone())),
+            format!("Team {} does not exist", team)
+        );
+        self.moderators_team = team;
+        self.assert_moderators_not_locked_out();
+    }
+
     pub fn get_root_members(&self) -> HashMap<Member, VersionedMemberMetadata> {
         self.access_control.members_list.get_root_members()
     }
@@ -53,27 +235,49 @@ impl Contract {
     pub fn add_member(&mut self, member: Member, metadata: VersionedMemberMetadata) {
         require!(
             self.has_moderator(env::predecessor_account_id())
-                || env::predecessor_account_id() == env::current_account_id(),
+                || self.is_owner(&env::predecessor_account_id()),
             "Only the admin and moderators can add members"
         );
-        self.access_control.members_list.add_member(member, metadata)
+        self.access_control.members_list.add_member(member.clone(), metadata.clone());
+        self.add_access_control_audit_entry(AccessControlChange::AddMember {
+            member,
+            after: metadata,
+        });
     }
 
     pub fn remove_member(&mut self, member: &Member) {
         require!(
             self.has_moderator(env::predecessor_account_id())
-                || env::predecessor_account_id() == env::current_account_id(),
+                || self.is_owner(&env::predecessor_account_id()),
             "Only the admin and moderators can remove members"
         );
-        self.access_control.members_list.remove_member(member)
+        self.assert_no_approval_required(SensitiveActionKind::RemoveMember);
+        self.remove_member_unchecked(member)
+    }
+
+    pub(crate) fn remove_member_unchecked(&mut self, member: &Member) {
+        let before = self.access_control.members_list.members.get(member).cloned();
+        self.access_control.members_list.remove_member(member);
+        self.assert_moderators_not_locked_out();
+        self.add_access_control_audit_entry(AccessControlChange::RemoveMember {
+            member: member.clone(),
+            before: before.unwrap(),
+        });
     }
 
     pub fn edit_member(&mut self, member: Member, metadata: VersionedMemberMetadata) {
         require!(
             self.has_moderator(env::predecessor_account_id())
-                || env::predecessor_account_id() == env::current_account_id(),
+                || self.is_owner(&env::predecessor_account_id()),
             "Only the admin and moderators can edit members"
         );
-        self.access_control.members_list.edit_member(member, metadata)
+        let before = self.access_control.members_list.members.get(&member).cloned();
+        self.access_control.members_list.edit_member(member.clone(), metadata.clone());
+        self.assert_moderators_not_locked_out();
+        self.add_access_control_audit_entry(AccessControlChange::EditMember {
+            member,
+            before: before.unwrap(),
+            after: metadata,
+        });
     }
 }
@@ -31,18 +31,27 @@ impl From<RuleMetadata> for VersionedRuleMetadata {
 #[derive(Clone, PartialOrd, PartialEq, Ord, Eq, Hash, Debug)]
 #[serde(from = "String", into = "String")]
 pub enum Rule {
-    /// Labels can be any string, but rules are created by the NEAR account owner of this contract,
-    /// or small circle of moderators. So this code cannot be abused. Likely creating a label that
-    /// mimics a rule makes this label only more restrictive, so there might be nothing to exploit.
-    /// TODO: Add extra logic to prevent malicious rules creation by creating labels that mimic rules.
+    /// Labels can be any string that does not mimic another rule, see `mimics_rule`.
     ExactMatch(String),
     StartsWith(String),
     Any(),
+    EndsWith(String),
+    /// `*` matches any sequence of characters, e.g. `wg-*-approved`.
+    Glob(String),
+    /// Applies to the labels the inner rule does not apply to.
+    Not(Box<Rule>),
 }
 
 /// JSON string representation prefix of Rule::StartsWith variant.
 const STARTS_WITH: &str = "starts-with:";
+/// JSON string representation prefix of Rule::EndsWith variant.
+const ENDS_WITH: &str = "ends-with:";
+/// JSON string representation prefix of Rule::Glob variant.
+const GLOB: &str = "glob:";
+/// JSON string representation prefix of Rule::Not variant, followed by the inner rule.
+const NOT: &str = "not:";
 const ANY: &str = "*";
+const GLOB_WILDCARD: char = '*';
 
 impl From<String> for Rule {
     fn from(full_str: String) -> Self {
@@ -50,6 +59,12 @@ impl From<String> for Rule {
             Rule::Any()
         } else if let Some(s) = full_str.strip_prefix(STARTS_WITH) {
             Rule::StartsWith(s.to_string())
+        } else if let Some(s) = full_str.strip_prefix(ENDS_WITH) {
+            Rule::EndsWith(s.to_string())
+        } else if let Some(s) = full_str.strip_prefix(GLOB) {
+            Rule::Glob(s.to_string())
+        } else if let Some(s) = full_str.strip_prefix(NOT) {
+            Rule::Not(Box::new(s.to_string().into()))
         } else {
             Rule::ExactMatch(full_str)
         }
@@ -62,10 +77,39 @@ impl Into<String> for Rule {
             Rule::ExactMatch(s) => s.to_string(),
             Rule::StartsWith(s) => format!("{}{}", STARTS_WITH, s).to_string(),
             Rule::Any() => ANY.to_string(),
+            Rule::EndsWith(s) => format!("{}{}", ENDS_WITH, s),
+            Rule::Glob(s) => format!("{}{}", GLOB, s),
+            Rule::Not(rule) => format!("{}{}", NOT, Into::<String>::into(*rule)),
         }
     }
 }
 
+/// Whether the label reads as a rule other than its exact match, e.g. `starts-with:funding`. A
+/// rule naming such a label would not apply to it, so these labels can't be used.
+pub fn mimics_rule(label: &str) -> bool {
+    Rule::from(label.to_string()) != Rule::ExactMatch(label.to_string())
+}
+
+fn glob_matches(pattern: &str, label: &str) -> bool {
+    let mut parts = pattern.split(GLOB_WILDCARD);
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = label.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    // Without a wildcard the pattern must match the whole label.
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
 impl Rule {
     /// Check if this rule applies to a label.
     pub fn applies(&self, label: &str) -> bool {
@@ -73,15 +117,18 @@ impl Rule {
             Rule::ExactMatch(rule) => label == rule,
             Rule::StartsWith(rule) => label.starts_with(rule),
             Rule::Any() => true,
+            Rule::EndsWith(rule) => label.ends_with(rule),
+            Rule::Glob(rule) => glob_matches(rule, label),
+            Rule::Not(rule) => !rule.applies(label),
         }
     }
 
-    /// Check if this rule applies to any of the labels.
+    /// Check if this rule applies to any of the labels. `Any` also applies when there are no
+    /// labels at all.
     pub fn applies_to_any(&self, labels: &[String]) -> bool {
         match self {
-            Rule::ExactMatch(rule) => labels.iter().any(|label| label == rule),
-            Rule::StartsWith(rule) => labels.iter().any(|label| label.starts_with(rule.as_str())),
             Rule::Any() => true,
+            _ => labels.iter().any(|label| self.applies(label)),
         }
     }
 }
@@ -94,22 +141,7 @@ impl RulesList {
 
     /// Get restricted labels out of this list.
     pub fn find_restricted(&self, labels: &[String]) -> HashSet<String> {
-        self.rules
-            .keys()
-            .map(|key| match key {
-                Rule::ExactMatch(rule) => {
-                    labels.iter().filter(|label| label == &rule).collect::<Vec<_>>()
-                }
-                Rule::StartsWith(rule) => {
-                    labels.iter().filter(|label| label.starts_with(rule)).collect::<Vec<_>>()
-                }
-                Rule::Any() => {
-                    vec![]
-                }
-            })
-            .flatten()
-            .cloned()
-            .collect()
+        labels.iter().filter(|label| self.is_restricted(label)).cloned().collect()
     }
 
     /// Set rules as restricted. Can be also used to override metadata on existing rules.
@@ -151,6 +183,55 @@ mod tests {
         assert_eq!(rule, Rule::StartsWith("funding".to_string()));
     }
 
+    #[test]
+    fn rule_serialization_round_trip() {
+        for (rule, rule_json) in [
+            (Rule::Any(), "*"),
+            (Rule::EndsWith("-approved".to_string()), "ends-with:-approved"),
+            (Rule::Glob("wg-*-approved".to_string()), "glob:wg-*-approved"),
+            (Rule::Not(Box::new(Rule::ExactMatch("wg-protocol".to_string()))), "not:wg-protocol"),
+            (
+                Rule::Not(Box::new(Rule::StartsWith("funding".to_string()))),
+                "not:starts-with:funding",
+            ),
+            (Rule::Not(Box::new(Rule::Not(Box::new(Rule::Any())))), "not:not:*"),
+        ] {
+            assert_eq!(serde_json::to_value(&rule).unwrap(), serde_json::json!(rule_json));
+            assert_eq!(serde_json::from_value::<Rule>(serde_json::json!(rule_json)).unwrap(), rule);
+        }
+    }
+
+    #[test]
+    fn rule_applies() {
+        let rule = Rule::EndsWith("-approved".to_string());
+        assert!(rule.applies("wg-protocol-approved"));
+        assert!(!rule.applies("wg-protocol-approved-not"));
+
+        let rule = Rule::Glob("wg-*-approved".to_string());
+        assert!(rule.applies("wg-protocol-approved"));
+        assert!(rule.applies("wg--approved"));
+        assert!(!rule.applies("wg-approved"));
+        assert!(!rule.applies("wg-protocol"));
+        assert!(!rule.applies("nowg-protocol-approved"));
+
+        let rule = Rule::Glob("wg-*-*-approved".to_string());
+        assert!(rule.applies("wg-a-b-approved"));
+        assert!(!rule.applies("wg-a-approved"));
+
+        let rule = Rule::Glob("wg-protocol".to_string());
+        assert!(rule.applies("wg-protocol"));
+        assert!(!rule.applies("wg-protocol-approved"));
+
+        let rule = Rule::Not(Box::new(Rule::StartsWith("wg-".to_string())));
+        assert!(rule.applies("funding"));
+        assert!(!rule.applies("wg-protocol"));
+        assert!(rule.applies_to_any(&["wg-protocol".to_string(), "funding".to_string()]));
+        assert!(!rule.applies_to_any(&["wg-protocol".to_string()]));
+        assert!(!rule.applies_to_any(&[]));
+
+        assert!(Rule::Any().applies_to_any(&[]));
+    }
+
     fn create_list() -> RulesList {
         RulesList {
             rules: HashMap::from([
@@ -234,4 +315,18 @@ mod tests {
         ]);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn find_restricted_with_any() {
+        let mut list = create_list();
+        list.set_restricted(RulesList {
+            rules: HashMap::from([(
+                Rule::Any(),
+                RuleMetadata { description: "Everything is restricted".to_string() }.into(),
+            )]),
+        });
+        let labels = ["wg-protocol".to_string(), "nofunding".to_string()];
+        assert!(list.is_restricted("nofunding"));
+        assert_eq!(list.find_restricted(&labels), HashSet::from(labels));
+    }
 }
@@ -36,4 +36,17 @@ pub enum StorageKey {
     RFPLinkedProposals,
     LabelInfo,
     ChangeLog,
+    ProposalWorkflow,
+    ProposalTimelineHistory,
+    RFPEvaluations,
+    AccessControlAuditLog,
+    PendingSensitiveActions,
+    CommunityMembers,
+    CommunityMembersOf(CryptoHash),
+    AccountCommunities,
+    CommunityAdminInvitations,
+    ArchivedCommunities,
+    CommunityAliases,
+    TagToCommunities,
+    CommunitySearchIndex,
 }
@@ -0,0 +1,171 @@
+use crate::access_control::members::ActionType;
+use crate::community::CommunityHandle;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId, Timestamp};
+
+/// One week.
+const COMMUNITY_ADMIN_INVITATION_TTL: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommunityAdminInvitation {
+    pub account_id: AccountId,
+    pub invited_by: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub expires_at: Timestamp,
+}
+
+impl CommunityAdminInvitation {
+    pub fn is_expired(&self) -> bool {
+        env::block_timestamp() > self.expires_at
+    }
+}
+
+impl Contract {
+    fn assert_can_manage_community_admins(&self, handle: &CommunityHandle) {
+        self.assert_community_not_archived(handle);
+        require!(
+            self.get_account_community_permissions(env::predecessor_account_id(), handle.clone())
+                .can_configure,
+            "Only community admins and hub moderators can manage community admins"
+        );
+    }
+
+    pub(crate) fn remove_community_admin_invitations(&mut self, handle: &CommunityHandle) {
+        self.community_admin_invitations.remove(handle);
+    }
+}
+
+#[near]
+impl Contract {
+    /// The first admin is the owner of the community.
+    pub fn get_community_owner(&self, handle: CommunityHandle) -> Option<AccountId> {
+        self.communities.get(&handle)?.admins.first().cloned()
+    }
+
+    /// Invitations that have not expired yet.
+    pub fn get_community_admin_invitations(
+        &self,
+        handle: CommunityHandle,
+    ) -> Vec<CommunityAdminInvitation> {
+        let mut invitations: Vec<_> = self
+            .community_admin_invitations
+            .get(&handle)
+            .unwrap_or_default()
+            .into_values()
+            .filter(|invitation| !invitation.is_expired())
+            .collect();
+        invitations.sort_by(|a, b| a.account_id.cmp(&b.account_id));
+        invitations
+    }
+
+    /// The account becomes an admin once it accepts the invitation. Inviting the account again
+    /// renews the invitation.
+    pub fn invite_community_admin(&mut self, handle: CommunityHandle, account_id: AccountId) {
+        self.assert_can_manage_community_admins(&handle);
+        let community = self.communities.get(&handle).unwrap();
+        require!(
+            !community.admins.contains(&account_id),
+            format!("{} is already an admin of the community", account_id)
+        );
+        let mut invitations = self.community_admin_invitations.get(&handle).unwrap_or_default();
+        invitations.retain(|_, invitation| !invitation.is_expired());
+        invitations.insert(
+            account_id.clone(),
+            CommunityAdminInvitation {
+                account_id,
+                invited_by: env::predecessor_account_id(),
+                expires_at: env::block_timestamp().saturating_add(COMMUNITY_ADMIN_INVITATION_TTL),
+            },
+        );
+        self.community_admin_invitations.insert(&handle, &invitations);
+    }
+
+    /// Withdraws the invitation of the account, expired or not.
+    pub fn cancel_community_admin_invitation(
+        &mut self,
+        handle: CommunityHandle,
+        account_id: AccountId,
+    ) {
+        self.assert_can_manage_community_admins(&handle);
+        let mut invitations = self.community_admin_invitations.get(&handle).unwrap_or_default();
+        require!(
+            invitations.remove(&account_id).is_some(),
+            format!("{} is not invited to be an admin of the community", account_id)
+        );
+        self.community_admin_invitations.insert(&handle, &invitations);
+    }
+
+    pub fn accept_community_admin(&mut self, handle: CommunityHandle) {
+        self.assert_community_not_archived(&handle);
+        let account_id = env::predecessor_account_id();
+        let mut invitations = self.community_admin_invitations.get(&handle).unwrap_or_default();
+        let invitation = invitations.remove(&account_id).unwrap_or_else(|| {
+            panic!("{} is not invited to be an admin of the community", account_id)
+        });
+        require!(!invitation.is_expired(), "The invitation has expired");
+        self.community_admin_invitations.insert(&handle, &invitations);
+
+        let mut community = self
+            .communities
+            .get(&handle)
+            .unwrap_or_else(|| panic!("Community with handle `{}` does not exist", handle));
+        if !community.admins.contains(&account_id) {
+            community.admins.push(account_id);
+        }
+        self.communities.insert(&handle, &community);
+    }
+
+    /// Admins can remove other admins and themselves, but not the owner. Hub moderators can also
+    /// remove the owner, in which case the next admin becomes the owner.
+    pub fn remove_community_admin(&mut self, handle: CommunityHandle, account_id: AccountId) {
+        self.assert_can_manage_community_admins(&handle);
+        let mut community = self.communities.get(&handle).unwrap();
+        require!(
+            community.admins.contains(&account_id),
+            format!("{} is not an admin of the community", account_id)
+        );
+        require!(community.admins.len() > 1, "A community must have at least one admin");
+        require!(
+            community.admins.first() != Some(&account_id)
+                || self.is_allowed_to(
+                    &env::predecessor_account_id(),
+                    ActionType::ModerateCommunities,
+                    vec![]
+                ),
+            "The owner of the community can't be removed, transfer the ownership first"
+        );
+        community.admins.retain(|admin| admin != &account_id);
+        self.communities.insert(&handle, &community);
+    }
+
+    /// Makes another admin the owner. The previous owner stays an admin.
+    pub fn transfer_community_ownership(&mut self, handle: CommunityHandle, account_id: AccountId) {
+        self.assert_community_not_archived(&handle);
+        let mut community = self
+            .communities
+            .get(&handle)
+            .unwrap_or_else(|| panic!("Community with handle `{}` does not exist", handle));
+        let predecessor = env::predecessor_account_id();
+        require!(
+            community.admins.first() == Some(&predecessor)
+                || self.is_allowed_to(&predecessor, ActionType::ModerateCommunities, vec![]),
+            "Only the owner of the community and hub moderators can transfer the ownership"
+        );
+        require!(
+            community.admins.contains(&account_id),
+            format!(
+                "{} must accept an admin invitation before becoming the owner of the community",
+                account_id
+            )
+        );
+        community.admins.retain(|admin| admin != &account_id);
+        community.admins.insert(0, account_id);
+        self.communities.insert(&handle, &community);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::access_control::members::ActionType;
+use crate::community::CommunityHandle;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId, Timestamp};
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommunityArchive {
+    pub archived_by: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub archived_at: Timestamp,
+}
+
+impl Contract {
+    /// Archived communities are read-only until a moderator restores them.
+    pub(crate) fn assert_community_not_archived(&self, handle: &CommunityHandle) {
+        require!(
+            !self.is_community_archived(handle.clone()),
+            format!("Community `{}` is archived and read-only", handle)
+        );
+    }
+
+    fn assert_can_archive_communities(&self) {
+        require!(
+            self.is_allowed_to(
+                &env::predecessor_account_id(),
+                ActionType::ModerateCommunities,
+                vec![]
+            ),
+            "Only moderators can archive and restore communities"
+        );
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn is_community_archived(&self, handle: CommunityHandle) -> bool {
+        self.archived_communities.get(&handle).is_some()
+    }
+
+    pub fn get_archived_communities(&self) -> Vec<(CommunityHandle, CommunityArchive)> {
+        let mut archived: Vec<_> = self.archived_communities.iter().collect();
+        archived.sort_by(|a, b| a.0.cmp(&b.0));
+        archived
+    }
+
+    /// Hides the community from the community lists and web4, and makes it read-only. Nothing is
+    /// removed, neither here nor on the community account.
+    pub fn archive_community(&mut self, handle: CommunityHandle) {
+        self.assert_can_archive_communities();
+        require!(
+            self.communities.get(&handle).is_some(),
+            format!("Community with handle `{}` does not exist", handle)
+        );
+        self.assert_community_not_archived(&handle);
+        self.archived_communities.insert(
+            &handle,
+            &CommunityArchive {
+                archived_by: env::predecessor_account_id(),
+                archived_at: env::block_timestamp(),
+            },
+        );
+    }
+
+    pub fn restore_community(&mut self, handle: CommunityHandle) {
+        self.assert_can_archive_communities();
+        require!(
+            self.archived_communities.remove(&handle).is_some(),
+            format!("Community `{}` is not archived", handle)
+        );
+    }
+}
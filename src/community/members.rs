@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use crate::access_control::members::ActionType;
+use crate::common::StorageKey;
+use crate::community::CommunityHandle;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::collections::UnorderedMap;
+use near_sdk::{env, near, require, AccountId, Promise, Timestamp};
+
+const DEFAULT_COMMUNITY_MEMBERS_PAGE_LIMIT: u64 = 50;
+/// How many members a single call of `remove_archived_community_members` removes, so that it
+/// fits into the gas limit of one transaction.
+const MAX_REMOVED_COMMUNITY_MEMBERS_PER_CALL: usize = 100;
+
+/// Roles are assigned by the community admins. They don't grant any permission on their own,
+/// community features decide what each role can do.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum CommunityRole {
+    Member,
+    Contributor,
+    Moderator,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommunityMembership {
+    pub role: CommunityRole,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub joined_at: Timestamp,
+}
+
+#[near(serializers=[json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommunityMember {
+    pub account_id: AccountId,
+    pub role: CommunityRole,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub joined_at: Timestamp,
+}
+
+fn new_community_members(handle: &CommunityHandle) -> UnorderedMap<AccountId, CommunityMembership> {
+    UnorderedMap::new(StorageKey::CommunityMembersOf(env::sha256_array(handle.as_bytes())))
+}
+
+impl Contract {
+    fn assert_community_exists(&self, handle: &CommunityHandle) {
+        require!(
+            self.communities.get(handle).is_some(),
+            format!("Community with handle `{}` does not exist", handle)
+        );
+    }
+
+    fn add_community_member(
+        &mut self,
+        handle: &CommunityHandle,
+        account_id: &AccountId,
+        membership: CommunityMembership,
+    ) {
+        let mut members =
+            self.community_members.get(handle).unwrap_or_else(|| new_community_members(handle));
+        members.insert(account_id, &membership);
+        self.community_members.insert(handle, &members);
+
+        let mut communities = self.resolve_account_communities(account_id);
+        communities.insert(handle.clone());
+        self.account_communities.insert(account_id, &communities);
+    }
+
+    fn remove_community_member(&mut self, handle: &CommunityHandle, account_id: &AccountId) {
+        if let Some(mut members) = self.community_members.get(handle) {
+            members.remove(account_id);
+            self.community_members.insert(handle, &members);
+        }
+        self.update_account_communities(account_id);
+    }
+
+    /// The reverse index is not updated when a community is renamed or loses its members, as
+    /// that would take a write per member. Its entries are checked against the members of the
+    /// community when they are read instead.
+    fn resolve_account_communities(&self, account_id: &AccountId) -> HashSet<CommunityHandle> {
+        self.account_communities
+            .get(account_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|handle| self.resolve_community_handle(handle))
+            .filter(|handle| {
+                self.community_members
+                    .get(handle)
+                    .map_or(false, |members| members.get(account_id).is_some())
+            })
+            .collect()
+    }
+
+    /// Drops the stale entries of the account together with the community it left.
+    fn update_account_communities(&mut self, account_id: &AccountId) {
+        let communities = self.resolve_account_communities(account_id);
+        if communities.is_empty() {
+            self.account_communities.remove(account_id);
+        } else {
+            self.account_communities.insert(account_id, &communities);
+        }
+    }
+
+    /// A community can have too many members to remove them in the same call that deletes it,
+    /// so they are removed beforehand with `remove_archived_community_members`.
+    pub(crate) fn remove_community_members(&mut self, handle: &CommunityHandle) {
+        require!(
+            self.get_community_members_count(handle.clone()) == 0,
+            "Remove the members of the community first with remove_archived_community_members"
+        );
+        self.community_members.remove(handle);
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_community_member(
+        &self,
+        handle: CommunityHandle,
+        account_id: AccountId,
+    ) -> Option<CommunityMember> {
+        let membership = self.community_members.get(&handle)?.get(&account_id)?;
+        Some(CommunityMember { account_id, role: membership.role, joined_at: membership.joined_at })
+    }
+
+    /// Members in the order they joined, as long as nobody left. Leaving moves the last member
+    /// into the freed slot.
+    pub fn get_community_members(
+        &self,
+        handle: CommunityHandle,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<CommunityMember> {
+        let Some(members) = self.community_members.get(&handle) else {
+            return vec![];
+        };
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_COMMUNITY_MEMBERS_PAGE_LIMIT);
+        let account_ids = members.keys_as_vector();
+        let memberships = members.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), members.len()))
+            .map(|index| {
+                let membership = memberships.get(index).unwrap();
+                CommunityMember {
+                    account_id: account_ids.get(index).unwrap(),
+                    role: membership.role,
+                    joined_at: membership.joined_at,
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_community_members_count(&self, handle: CommunityHandle) -> u64 {
+        self.community_members.get(&handle).map_or(0, |members| members.len())
+    }
+
+    pub fn get_account_communities(&self, account_id: AccountId) -> Vec<CommunityHandle> {
+        let mut communities: Vec<_> =
+            self.resolve_account_communities(&account_id).into_iter().collect();
+        communities.sort();
+        communities
+    }
+
+    /// The attached deposit pays for the storage of the membership, the rest is refunded.
+    #[payable]
+    pub fn join_community(&mut self, handle: CommunityHandle) {
+        self.assert_community_exists(&handle);
+        self.assert_community_not_archived(&handle);
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.get_community_member(handle.clone(), account_id.clone()).is_none(),
+            "The account is already a member of the community"
+        );
+        let initial_storage_usage = env::storage_usage();
+        self.add_community_member(
+            &handle,
+            &account_id,
+            CommunityMembership { role: CommunityRole::Member, joined_at: env::block_timestamp() },
+        );
+        let storage_cost = env::storage_byte_cost()
+            .saturating_mul(env::storage_usage().saturating_sub(initial_storage_usage).into());
+        require!(
+            env::attached_deposit() >= storage_cost,
+            format!(
+                "Require {} yoctoNEAR to pay for the storage of the membership",
+                storage_cost.as_yoctonear()
+            )
+        );
+        let refund = env::attached_deposit().saturating_sub(storage_cost);
+        if !refund.is_zero() {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    pub fn leave_community(&mut self, handle: CommunityHandle) {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.get_community_member(handle.clone(), account_id.clone()).is_some(),
+            "The account is not a member of the community"
+        );
+        self.remove_community_member(&handle, &account_id);
+    }
+
+    /// Only community admins and hub moderators can assign roles, and only to accounts that
+    /// joined the community. Members can still leave an archived community, but roles can't
+    /// change.
+    pub fn set_community_member_role(
+        &mut self,
+        handle: CommunityHandle,
+        account_id: AccountId,
+        role: CommunityRole,
+    ) {
+        self.assert_community_not_archived(&handle);
+        require!(
+            self.get_account_community_permissions(env::predecessor_account_id(), handle.clone())
+                .can_configure,
+            "Only community admins and hub moderators can assign community roles"
+        );
+        let member = self
+            .get_community_member(handle.clone(), account_id.clone())
+            .unwrap_or_else(|| panic!("{} is not a member of the community", account_id));
+        self.add_community_member(
+            &handle,
+            &account_id,
+            CommunityMembership { role, joined_at: member.joined_at },
+        );
+    }
+
+    /// Removes a batch of members of an archived community, so that it can be deleted. Returns
+    /// how many members are left, it should be called again until it returns zero.
+    pub fn remove_archived_community_members(&mut self, handle: CommunityHandle) -> u32 {
+        require!(
+            self.is_allowed_to(
+                &env::predecessor_account_id(),
+                ActionType::ModerateCommunities,
+                vec![]
+            ),
+            "Only moderators can remove the members of archived communities"
+        );
+        require!(
+            self.is_community_archived(handle.clone()),
+            format!("Community `{}` is not archived", handle)
+        );
+        let Some(mut members) = self.community_members.get(&handle) else {
+            return 0;
+        };
+        let account_ids: Vec<_> =
+            members.keys().take(MAX_REMOVED_COMMUNITY_MEMBERS_PER_CALL).collect();
+        for account_id in &account_ids {
+            members.remove(account_id);
+        }
+        self.community_members.insert(&handle, &members);
+        members.len() as u32
+    }
+}
@@ -1,5 +1,12 @@
 use near_sdk::{env, ext_contract, near, require, AccountId, Gas, NearToken};
 
+pub mod admins;
+pub mod archive;
+pub mod members;
+pub mod rename;
+pub mod search;
+pub mod teams;
+
 pub type CommunityHandle = String;
 
 pub type AddOnId = String;
@@ -207,5 +214,6 @@ pub const CREATE_DISCUSSION_BALANCE: NearToken = NearToken::from_near(2);
 pub const CREATE_COMMUNITY_GAS: Gas = Gas::from_tgas(200);
 pub const UPDATE_COMMUNITY_GAS: Gas = Gas::from_tgas(30);
 pub const DELETE_COMMUNITY_GAS: Gas = Gas::from_tgas(30);
+pub const RENAME_COMMUNITY_GAS: Gas = Gas::from_tgas(250);
 pub const SET_COMMUNITY_SOCIALDB_GAS: Gas = Gas::from_tgas(30);
 pub const CREATE_DISCUSSION_GAS: Gas = Gas::from_tgas(30);
@@ -0,0 +1,240 @@
+use crate::access_control::audit::AccessControlChange;
+use crate::access_control::members::Member;
+use crate::community::teams::community_label_prefix;
+use crate::community::*;
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId, Promise, PromiseResult, Timestamp};
+
+/// 30 days.
+const COMMUNITY_ACCOUNT_RELEASE_GRACE_PERIOD: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// The old handle of a renamed community.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommunityAlias {
+    /// The handle the community was renamed to. It can be an alias too if the community was
+    /// renamed again.
+    pub handle: CommunityHandle,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub renamed_at: Timestamp,
+    /// The account of the old handle can be released after this timestamp.
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub release_after: Timestamp,
+    pub released: bool,
+}
+
+impl Contract {
+    /// Follows the aliases of renamed communities to the current handle.
+    pub(crate) fn resolve_community_handle(
+        &self,
+        handle: &CommunityHandle,
+    ) -> Option<CommunityHandle> {
+        let mut handle = handle.clone();
+        for _ in 0..=self.community_aliases.len() {
+            if self.communities.get(&handle).is_some() {
+                return Some(handle);
+            }
+            handle = self.community_aliases.get(&handle)?.handle;
+        }
+        None
+    }
+
+    /// Old handles that resolve to the community.
+    pub(crate) fn get_community_aliases(&self, handle: &CommunityHandle) -> Vec<CommunityHandle> {
+        let mut aliases: Vec<_> = self
+            .community_aliases
+            .keys()
+            .filter(|alias| self.resolve_community_handle(alias).as_ref() == Some(handle))
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// Removes the old handles of a deleted community, so that they can't be released once
+    /// another community takes them. Returns the old handles whose accounts were not released
+    /// yet, they go away together with the community account.
+    pub(crate) fn remove_community_aliases(
+        &mut self,
+        handle: &CommunityHandle,
+    ) -> Vec<CommunityHandle> {
+        let mut unreleased = vec![];
+        for alias in self.get_community_aliases(handle) {
+            if let Some(CommunityAlias { released: false, .. }) =
+                self.community_aliases.remove(&alias)
+            {
+                unreleased.push(alias);
+            }
+        }
+        unreleased
+    }
+
+    /// Moves the community and everything keyed by its handle to the new handle, and leaves an
+    /// alias behind.
+    fn move_community(&mut self, handle: &CommunityHandle, new_handle: &CommunityHandle) {
+        let mut community = self.communities.remove(handle).unwrap();
+        self.remove_community_from_indexes(&community);
+        community.handle = new_handle.clone();
+        self.communities.insert(new_handle, &community);
+        self.add_community_to_indexes(&community);
+
+        for featured in self.featured_communities.iter_mut() {
+            if &featured.handle == handle {
+                featured.handle = new_handle.clone();
+            }
+        }
+
+        if let Some(invitations) = self.community_admin_invitations.remove(handle) {
+            self.community_admin_invitations.insert(new_handle, &invitations);
+        }
+
+        // The reverse index of the members keeps the old handle, it is resolved through the alias.
+        if let Some(members) = self.community_members.remove(handle) {
+            self.community_members.insert(new_handle, &members);
+        }
+
+        let mut teams: Vec<_> = self.get_community_teams(handle.clone()).into_keys().collect();
+        teams.sort();
+        for team in teams {
+            let Member::Team(name) = &team else { continue };
+            let new_team = Member::Team(format!(
+                "{}{}",
+                community_label_prefix(new_handle),
+                &name[handle.len() + 1..]
+            ));
+            self.access_control.members_list.rename_member(&team, new_team.clone());
+            self.add_access_control_audit_entry(AccessControlChange::RenameMember {
+                member: team,
+                new_member: new_team,
+            });
+        }
+
+        self.community_aliases.remove(new_handle);
+        self.community_aliases.insert(
+            handle,
+            &CommunityAlias {
+                handle: new_handle.clone(),
+                renamed_at: env::block_timestamp(),
+                release_after: env::block_timestamp()
+                    .saturating_add(COMMUNITY_ACCOUNT_RELEASE_GRACE_PERIOD),
+                released: false,
+            },
+        );
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_community_alias(&self, handle: CommunityHandle) -> Option<CommunityAlias> {
+        self.community_aliases.get(&handle)
+    }
+
+    /// Renames the community and creates the account of the new handle through the factory,
+    /// then writes the community profiles under it. The old handle stays as an alias, and its
+    /// account can be released with `release_community_account` after a grace period.
+    ///
+    /// A community can take back one of its old handles. The account of the old handle is reused
+    /// if it was not released yet.
+    #[payable]
+    pub fn rename_community(
+        &mut self,
+        handle: CommunityHandle,
+        new_handle: CommunityHandle,
+    ) -> Promise {
+        require!(
+            self.get_account_community_permissions(env::predecessor_account_id(), handle.clone())
+                .can_configure,
+            "Only community admins and hub moderators can rename communities"
+        );
+        self.assert_community_not_archived(&handle);
+        require!(handle != new_handle, "The community already has this handle");
+        require!(
+            self.communities.get(&new_handle).is_none()
+                && self.community_aliases.get(&new_handle).map_or(true, |_| {
+                    self.resolve_community_handle(&new_handle).as_ref() == Some(&handle)
+                }),
+            "Community already exists"
+        );
+        require!(env::prepaid_gas() >= RENAME_COMMUNITY_GAS, "Require at least 250 Tgas");
+
+        let previous_alias = self.community_aliases.get(&new_handle);
+        let reused_account = previous_alias.as_ref().map_or(false, |alias| !alias.released);
+        self.move_community(&handle, &new_handle);
+        let community = self.communities.get(&new_handle).unwrap();
+        community.validate();
+
+        if reused_account {
+            return self.set_community_profiles(&community);
+        }
+        require!(
+            env::attached_deposit() >= CREATE_COMMUNITY_BALANCE,
+            "Require 4 NEAR to create the community account"
+        );
+        ext_devhub_community_factory::ext(get_devhub_community_factory())
+            .with_unused_gas_weight(1)
+            .with_attached_deposit(CREATE_COMMUNITY_BALANCE)
+            .create_community_account(new_handle.clone())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(UPDATE_COMMUNITY_GAS)
+                    .on_community_account_created(
+                        handle,
+                        new_handle,
+                        previous_alias,
+                        env::predecessor_account_id(),
+                    ),
+            )
+    }
+
+    /// Writes the profiles under the new account, or takes the rename back and refunds the
+    /// deposit if the account could not be created.
+    #[private]
+    pub fn on_community_account_created(
+        &mut self,
+        handle: CommunityHandle,
+        new_handle: CommunityHandle,
+        previous_alias: Option<CommunityAlias>,
+        renamed_by: AccountId,
+    ) -> Promise {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            let community = self.communities.get(&new_handle).unwrap();
+            return self.set_community_profiles(&community);
+        }
+        self.move_community(&new_handle, &handle);
+        match previous_alias {
+            Some(alias) => self.community_aliases.insert(&new_handle, &alias),
+            None => self.community_aliases.remove(&new_handle),
+        };
+        Promise::new(renamed_by).transfer(CREATE_COMMUNITY_BALANCE)
+    }
+
+    /// Deletes the account of an old handle once the grace period is over. Anyone can call it.
+    pub fn release_community_account(&mut self, handle: CommunityHandle) -> Promise {
+        let mut alias = self
+            .community_aliases
+            .get(&handle)
+            .unwrap_or_else(|| panic!("`{}` is not an old community handle", handle));
+        require!(!alias.released, "The community account was already released");
+        require!(
+            self.communities.get(&handle).is_none(),
+            format!("`{}` is the handle of an existing community", handle)
+        );
+        require!(
+            env::block_timestamp() > alias.release_after,
+            "The grace period of the community account is not over yet"
+        );
+        require!(env::prepaid_gas() >= DELETE_COMMUNITY_GAS, "Require at least 30 Tgas");
+        alias.released = true;
+        self.community_aliases.insert(&handle, &alias);
+        ext_devhub_community::ext(get_devhub_community_account(&handle).parse().unwrap())
+            .with_unused_gas_weight(1)
+            .destroy()
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Bound;
+
+use crate::community::{Community, CommunityHandle, CommunityMetadata};
+use crate::{Contract, ContractExt};
+
+use near_sdk::near;
+
+const DEFAULT_COMMUNITIES_PAGE_LIMIT: u64 = 50;
+
+impl From<Community> for CommunityMetadata {
+    fn from(community: Community) -> Self {
+        CommunityMetadata {
+            admins: community.admins,
+            handle: community.handle,
+            name: community.name,
+            tag: community.tag,
+            description: community.description,
+            logo_url: community.logo_url,
+            banner_url: community.banner_url,
+            bio_markdown: community.bio_markdown,
+        }
+    }
+}
+
+/// The lowercase handle and name of the community, the terms a search query is a prefix of.
+pub(crate) fn community_search_terms(handle: &CommunityHandle, name: &str) -> [String; 2] {
+    [handle.to_lowercase(), name.to_lowercase()]
+}
+
+impl Contract {
+    /// Adds the community to the tag index and the search index.
+    pub(crate) fn add_community_to_indexes(&mut self, community: &Community) {
+        let mut handles = self.tag_to_communities.get(&community.tag).unwrap_or_default();
+        handles.insert(community.handle.clone());
+        self.tag_to_communities.insert(&community.tag, &handles);
+
+        for term in community_search_terms(&community.handle, &community.name) {
+            let mut handles = self.community_search_index.get(&term).unwrap_or_default();
+            handles.insert(community.handle.clone());
+            self.community_search_index.insert(&term, &handles);
+        }
+    }
+
+    pub(crate) fn remove_community_from_indexes(&mut self, community: &Community) {
+        let mut handles = self.tag_to_communities.get(&community.tag).unwrap_or_default();
+        handles.remove(&community.handle);
+        if handles.is_empty() {
+            self.tag_to_communities.remove(&community.tag);
+        } else {
+            self.tag_to_communities.insert(&community.tag, &handles);
+        }
+
+        for term in community_search_terms(&community.handle, &community.name) {
+            let mut handles = self.community_search_index.get(&term).unwrap_or_default();
+            handles.remove(&community.handle);
+            if handles.is_empty() {
+                self.community_search_index.remove(&term);
+            } else {
+                self.community_search_index.insert(&term, &handles);
+            }
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Communities with the tag, sorted by handle. Archived communities are left out.
+    pub fn get_communities_by_tag(&self, tag: String) -> Vec<CommunityMetadata> {
+        let mut handles: Vec<_> =
+            self.tag_to_communities.get(&tag).unwrap_or_default().into_iter().collect();
+        handles.sort();
+        handles
+            .into_iter()
+            .filter(|handle| !self.is_community_archived(handle.clone()))
+            .filter_map(|handle| self.communities.get(&handle))
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Without a query, pages through all the communities, archived communities are left out of
+    /// the page. With a query, the communities with exactly this tag come first, sorted by handle,
+    /// followed by the ones whose handle or name starts with it, ignoring case, in the order of
+    /// the matching handle or name. Archived communities are left out.
+    pub fn get_communities(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        query: Option<String>,
+    ) -> Vec<CommunityMetadata> {
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_COMMUNITIES_PAGE_LIMIT);
+        let Some(query) = query.filter(|query| !query.is_empty()) else {
+            let handles = self.communities.keys_as_vector();
+            let communities = self.communities.values_as_vector();
+            return (from_index..std::cmp::min(from_index.saturating_add(limit), handles.len()))
+                .filter(|index| !self.is_community_archived(handles.get(*index).unwrap()))
+                .map(|index| communities.get(index).unwrap().into())
+                .collect();
+        };
+
+        let mut tagged: Vec<_> =
+            self.tag_to_communities.get(&query).unwrap_or_default().into_iter().collect();
+        tagged.sort();
+        let mut seen: HashSet<_> = tagged.iter().cloned().collect();
+
+        // The search index is sorted, so the terms that start with the query are next to each
+        // other. The scan stops as soon as the page is full, and only the communities of the
+        // page are loaded.
+        let prefix = query.to_lowercase();
+        let prefixed = self
+            .community_search_index
+            .range((Bound::Included(prefix.clone()), Bound::Unbounded))
+            .take_while(move |(term, _)| term.starts_with(&prefix))
+            .flat_map(|(_, handles)| handles.into_iter().collect::<BTreeSet<_>>())
+            .filter(move |handle| seen.insert(handle.clone()));
+
+        tagged
+            .into_iter()
+            .chain(prefixed)
+            .filter(|handle| !self.is_community_archived(handle.clone()))
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|handle| self.communities.get(&handle))
+            .map(Into::into)
+            .collect()
+    }
+}
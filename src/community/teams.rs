@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::access_control::audit::AccessControlChange;
+use crate::access_control::members::{ActionType, Member, MemberMetadata, VersionedMemberMetadata};
+use crate::access_control::rules::Rule;
+use crate::community::CommunityHandle;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId};
+
+/// Actions a community team can be granted. Both of them are tied to labels, so a community
+/// team can't get any hub-wide power.
+const COMMUNITY_TEAM_ACTIONS: [ActionType; 2] = [ActionType::EditPost, ActionType::UseLabels];
+
+/// Community teams are access control teams named `<handle>/<team>`.
+pub fn community_team(handle: &CommunityHandle, team: &str) -> Member {
+    Member::Team(format!("{}/{}", handle, team))
+}
+
+/// Labels of the community start with `<handle>/`, e.g. `devhub/reviewed`.
+pub fn community_label_prefix(handle: &CommunityHandle) -> String {
+    format!("{}/", handle)
+}
+
+fn is_community_team(handle: &CommunityHandle, member: &Member) -> bool {
+    matches!(member, Member::Team(team) if team.starts_with(&community_label_prefix(handle)))
+}
+
+/// Whether every label the rule applies to is under one of the prefixes.
+fn is_community_rule(prefixes: &[String], rule: &Rule) -> bool {
+    let starts_with_prefix = |label: &str| prefixes.iter().any(|prefix| label.starts_with(prefix));
+    match rule {
+        Rule::ExactMatch(label) => starts_with_prefix(label),
+        Rule::StartsWith(label) => starts_with_prefix(label),
+        Rule::Glob(pattern) => starts_with_prefix(pattern.split('*').next().unwrap_or_default()),
+        Rule::Any() | Rule::EndsWith(_) | Rule::Not(_) => false,
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_allowed_to_manage_community_teams(
+        &self,
+        account_id: &AccountId,
+        handle: &CommunityHandle,
+    ) -> bool {
+        self.get_account_community_permissions(account_id.clone(), handle.clone()).can_configure
+    }
+
+    fn assert_can_manage_community_teams(&self, handle: &CommunityHandle) {
+        self.assert_community_not_archived(handle);
+        require!(
+            self.is_allowed_to_manage_community_teams(&env::predecessor_account_id(), handle),
+            "Only community admins and hub moderators can manage community teams"
+        );
+    }
+
+    /// Community teams only grant label permissions under the community prefix, or the prefix of
+    /// an old handle so that renaming the community keeps the labels usable, can only inherit
+    /// from teams of the same community, and only have accounts and teams of the same community
+    /// as children.
+    fn assert_valid_community_team(
+        &self,
+        handle: &CommunityHandle,
+        team: &str,
+        metadata: &VersionedMemberMetadata,
+    ) {
+        require!(
+            matches!(team.chars().count(), 1..=40)
+                && team
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'),
+            "Community team name must contain 1 to 40 lowercase alphanumeric symbols, `_` or `-`"
+        );
+        let metadata = metadata.last_version();
+        let prefixes: Vec<_> = std::iter::once(handle)
+            .chain(self.get_community_aliases(handle).iter())
+            .map(community_label_prefix)
+            .collect();
+        for (rule, actions) in &metadata.permissions {
+            require!(
+                is_community_rule(&prefixes, rule),
+                format!(
+                    "Community team rules must only apply to labels starting with `{}`",
+                    community_label_prefix(handle)
+                )
+            );
+            require!(
+                actions.iter().all(|action| COMMUNITY_TEAM_ACTIONS.contains(action)),
+                "Community teams can only be granted edit-post and use-labels"
+            );
+        }
+        for parent in &metadata.parents {
+            require!(
+                is_community_team(handle, parent),
+                "Community teams can only have teams of the same community as parents"
+            );
+        }
+        for child in &metadata.children {
+            require!(
+                matches!(child, Member::Account(_)) || is_community_team(handle, child),
+                "Community teams can only have accounts and teams of the same community as children"
+            );
+        }
+    }
+
+    fn assert_not_moderators_team(&self, member: &Member) {
+        require!(
+            member != &Member::Team(self.moderators_team.clone()),
+            "The moderators team can't be managed as a community team"
+        );
+    }
+
+    /// Accounts don't have to be members already to be added to a community team.
+    fn add_missing_community_team_accounts(&mut self, metadata: &VersionedMemberMetadata) {
+        for child in metadata.last_version().children {
+            if matches!(child, Member::Account(_))
+                && !self.access_control.members_list.members.contains_key(&child)
+            {
+                let metadata: VersionedMemberMetadata = MemberMetadata::default().into();
+                self.access_control.members_list.add_member(child.clone(), metadata.clone());
+                self.add_access_control_audit_entry(AccessControlChange::AddMember {
+                    member: child,
+                    after: metadata,
+                });
+            }
+        }
+    }
+
+    /// Removes the teams of a deleted community. The accounts stay members.
+    pub(crate) fn remove_community_teams(&mut self, handle: &CommunityHandle) {
+        let mut teams: Vec<_> = self.get_community_teams(handle.clone()).into_keys().collect();
+        teams.sort();
+        for team in teams {
+            self.remove_member_unchecked(&team);
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_community_teams(
+        &self,
+        handle: CommunityHandle,
+    ) -> HashMap<Member, VersionedMemberMetadata> {
+        self.access_control
+            .members_list
+            .members
+            .iter()
+            .filter(|(member, _)| is_community_team(&handle, member))
+            .map(|(member, metadata)| (member.clone(), metadata.clone()))
+            .collect()
+    }
+
+    pub fn add_community_team(
+        &mut self,
+        handle: CommunityHandle,
+        team: String,
+        metadata: VersionedMemberMetadata,
+    ) {
+        self.assert_can_manage_community_teams(&handle);
+        self.assert_valid_community_team(&handle, &team, &metadata);
+        self.add_missing_community_team_accounts(&metadata);
+        let member = community_team(&handle, &team);
+        self.access_control.members_list.add_member(member.clone(), metadata.clone());
+        self.add_access_control_audit_entry(AccessControlChange::AddMember {
+            member,
+            after: metadata,
+        });
+    }
+
+    pub fn edit_community_team(
+        &mut self,
+        handle: CommunityHandle,
+        team: String,
+        metadata: VersionedMemberMetadata,
+    ) {
+        self.assert_can_manage_community_teams(&handle);
+        self.assert_valid_community_team(&handle, &team, &metadata);
+        let member = community_team(&handle, &team);
+        self.assert_not_moderators_team(&member);
+        let before = self
+            .access_control
+            .members_list
+            .members
+            .get(&member)
+            .cloned()
+            .unwrap_or_else(|| panic!("Community team {} does not exist", team));
+        self.add_missing_community_team_accounts(&metadata);
+        self.access_control.members_list.edit_member(member.clone(), metadata.clone());
+        self.assert_moderators_not_locked_out();
+        self.add_access_control_audit_entry(AccessControlChange::EditMember {
+            member,
+            before,
+            after: metadata,
+        });
+    }
+
+    pub fn remove_community_team(&mut self, handle: CommunityHandle, team: String) {
+        self.assert_can_manage_community_teams(&handle);
+        let member = community_team(&handle, &team);
+        self.assert_not_moderators_team(&member);
+        require!(
+            self.access_control.members_list.members.contains_key(&member),
+            format!("Community team {} does not exist", team)
+        );
+        self.remove_member_unchecked(&member);
+    }
+}
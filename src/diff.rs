@@ -0,0 +1,74 @@
+use crate::proposal::ProposalSnapshot;
+use crate::rfp::RFPSnapshot;
+
+use near_sdk::near;
+use near_sdk::serde_json::{json, Value};
+
+/// A field that differs between two snapshots, with its JSON value in each of them.
+#[near(serializers=[json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+type SnapshotFields = Vec<(&'static str, Value)>;
+
+fn sorted_labels<'a>(labels: impl Iterator<Item = &'a String>) -> Value {
+    let mut labels: Vec<_> = labels.collect();
+    labels.sort();
+    json!(labels)
+}
+
+fn proposal_snapshot_fields(snapshot: &ProposalSnapshot) -> SnapshotFields {
+    let body = snapshot.body.clone().latest_version();
+    let requested_sponsorship_usd_amount = body.requested_sponsorship_usd_amount().to_string();
+    vec![
+        ("name", json!(body.name)),
+        ("summary", json!(body.summary)),
+        ("description", json!(body.description)),
+        ("labels", sorted_labels(snapshot.labels.iter())),
+        ("timeline", json!(body.timeline.latest_version())),
+        ("requested_sponsorship_usd_amount", json!(requested_sponsorship_usd_amount)),
+        ("milestones", json!(body.milestones)),
+        (
+            "requested_sponsorship_paid_in_currency",
+            json!(body.requested_sponsorship_paid_in_currency),
+        ),
+        ("linked_rfp", json!(body.linked_rfp)),
+    ]
+}
+
+fn rfp_snapshot_fields(snapshot: &RFPSnapshot) -> SnapshotFields {
+    let body = snapshot.body.clone().latest_version();
+    vec![
+        ("name", json!(body.name)),
+        ("summary", json!(body.summary)),
+        ("description", json!(body.description)),
+        ("labels", sorted_labels(snapshot.labels.iter())),
+        ("timeline", json!(body.timeline)),
+        (
+            "total_budget_usd_amount",
+            json!(body.total_budget_usd_amount.map(|amount| amount.to_string())),
+        ),
+        ("budget_paid_in_currency", json!(body.budget_paid_in_currency)),
+        ("selected_proposals", json!(body.selected_proposals)),
+    ]
+}
+
+fn diff_fields(from: SnapshotFields, to: SnapshotFields) -> Vec<FieldChange> {
+    from.into_iter()
+        .zip(to)
+        .filter(|((_, from), (_, to))| from != to)
+        .map(|((field, from), (_, to))| FieldChange { field: field.to_string(), from, to })
+        .collect()
+}
+
+pub fn diff_proposal_snapshots(from: &ProposalSnapshot, to: &ProposalSnapshot) -> Vec<FieldChange> {
+    diff_fields(proposal_snapshot_fields(from), proposal_snapshot_fields(to))
+}
+
+pub fn diff_rfp_snapshots(from: &RFPSnapshot, to: &RFPSnapshot) -> Vec<FieldChange> {
+    diff_fields(rfp_snapshot_fields(from), rfp_snapshot_fields(to))
+}
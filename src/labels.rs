@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::access_control::audit::AccessControlChange;
+use crate::access_control::members::ActionType;
+use crate::access_control::rules::{mimics_rule, Rule, RulesList};
+use crate::changelog::ChangeLogType;
+use crate::proposal::{Proposal, ProposalId};
+use crate::rfp::{RFPId, RFP};
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require};
+
+/// How many proposals and RFPs a single call of a label lifecycle method relabels, so that it
+/// fits into the gas limit of one transaction and does not push more entries than the change
+/// log keeps.
+const MAX_RELABELED_ENTRIES_PER_CALL: usize = 50;
+
+pub(crate) fn assert_labels_not_rules<'a>(labels: impl IntoIterator<Item = &'a String>) {
+    for label in labels {
+        require!(
+            !mimics_rule(label),
+            format!("Label {} can't be used, it reads as an access control rule", label)
+        );
+    }
+}
+
+impl Contract {
+    fn is_label_registered(&self, label: &str) -> bool {
+        self.global_labels_info.get().contains_key(label)
+    }
+
+    fn assert_can_manage_labels(&self) {
+        require!(
+            self.is_allowed_to(&env::predecessor_account_id(), ActionType::ManageLabels, vec![]),
+            "Only the admin and moderators can manage labels"
+        );
+    }
+
+    /// Proposals only need registered labels when the contract setting says so.
+    pub(crate) fn assert_proposal_labels_registered<'a>(
+        &self,
+        labels: impl IntoIterator<Item = &'a String>,
+    ) {
+        if !self.require_registered_proposal_labels {
+            return;
+        }
+        for label in labels {
+            require!(self.is_label_registered(label), format!("Label {} is not registered", label));
+        }
+    }
+
+    /// Deprecated labels stay on the proposals and RFPs that have them, but can't be added to
+    /// others.
+    pub(crate) fn assert_labels_not_deprecated<'a>(
+        &self,
+        labels: impl IntoIterator<Item = &'a String>,
+    ) {
+        for label in labels {
+            require!(
+                !self.deprecated_labels.contains(label),
+                format!("Label {} is deprecated", label)
+            );
+        }
+    }
+
+    /// Every restricted rule that applies to the label must apply to the label that replaces it,
+    /// except its own exact match rule when that is carried over.
+    fn assert_restrictions_kept(&self, label: &str, new_label: &str, carry_exact_rule: bool) {
+        let exact_rule = Rule::ExactMatch(label.to_string());
+        for rule in self.access_control.rules_list.rules.keys() {
+            require!(
+                (carry_exact_rule && rule == &exact_rule)
+                    || !rule.applies(label)
+                    || rule.applies(new_label),
+                format!(
+                    "Label {} is restricted by a rule that does not apply to {}",
+                    label, new_label
+                )
+            );
+        }
+    }
+
+    /// Replaces the label in the current snapshots of the proposals and in the labels index. The
+    /// snapshot history is left untouched.
+    fn replace_proposals_label(
+        &mut self,
+        proposal_ids: &[ProposalId],
+        old_label: &String,
+        new_label: Option<&String>,
+    ) {
+        for proposal_id in proposal_ids {
+            let mut proposal: Proposal = self.get_proposal(*proposal_id).into();
+            proposal.snapshot.labels.remove(old_label);
+            if let Some(new_label) = new_label {
+                proposal.snapshot.labels.insert(new_label.clone());
+            }
+            self.proposals.replace((*proposal_id).into(), &proposal.into());
+            self.add_change_log(ChangeLogType::Proposal(*proposal_id));
+        }
+        if let Some(new_label) = new_label {
+            let mut proposals = self.label_to_proposals.get(new_label).unwrap_or_default();
+            proposals.extend(proposal_ids);
+            self.label_to_proposals.insert(new_label, &proposals);
+        }
+        let mut proposals = self.label_to_proposals.get(old_label).unwrap_or_default();
+        for proposal_id in proposal_ids {
+            proposals.remove(proposal_id);
+        }
+        if proposals.is_empty() {
+            self.label_to_proposals.remove(old_label);
+        } else {
+            self.label_to_proposals.insert(old_label, &proposals);
+        }
+    }
+
+    /// Same as `replace_proposals_label`, but for RFPs.
+    fn replace_rfps_label(
+        &mut self,
+        rfp_ids: &[RFPId],
+        old_label: &String,
+        new_label: Option<&String>,
+    ) {
+        for rfp_id in rfp_ids {
+            let mut rfp: RFP = self.load_rfp(*rfp_id);
+            rfp.snapshot.labels.remove(old_label);
+            if let Some(new_label) = new_label {
+                rfp.snapshot.labels.insert(new_label.clone());
+            }
+            self.rfps.replace((*rfp_id).into(), &rfp.into());
+            self.add_change_log(ChangeLogType::RFP(*rfp_id));
+        }
+        if let Some(new_label) = new_label {
+            let mut rfps = self.label_to_rfps.get(new_label).unwrap_or_default();
+            rfps.extend(rfp_ids);
+            self.label_to_rfps.insert(new_label, &rfps);
+        }
+        let mut rfps = self.label_to_rfps.get(old_label).unwrap_or_default();
+        for rfp_id in rfp_ids {
+            rfps.remove(rfp_id);
+        }
+        if rfps.is_empty() {
+            self.label_to_rfps.remove(old_label);
+        } else {
+            self.label_to_rfps.insert(old_label, &rfps);
+        }
+    }
+
+    /// Relabels at most `limit` proposals. Returns how many were relabeled.
+    fn replace_proposals_label_in_batch(
+        &mut self,
+        old_label: &String,
+        new_label: Option<&String>,
+        limit: usize,
+    ) -> usize {
+        let proposal_ids: Vec<ProposalId> = self
+            .label_to_proposals
+            .get(old_label)
+            .unwrap_or_default()
+            .into_iter()
+            .take(limit)
+            .collect();
+        self.replace_proposals_label(&proposal_ids, old_label, new_label);
+        proposal_ids.len()
+    }
+
+    /// Relabels the next batch of proposals and RFPs. Returns how many of them still have the old
+    /// label.
+    fn replace_label_in_batch(&mut self, old_label: &String, new_label: Option<&String>) -> u32 {
+        let relabeled_proposals = self.replace_proposals_label_in_batch(
+            old_label,
+            new_label,
+            MAX_RELABELED_ENTRIES_PER_CALL,
+        );
+
+        let rfp_ids: Vec<RFPId> = self
+            .label_to_rfps
+            .get(old_label)
+            .unwrap_or_default()
+            .into_iter()
+            .take(MAX_RELABELED_ENTRIES_PER_CALL - relabeled_proposals)
+            .collect();
+        self.replace_rfps_label(&rfp_ids, old_label, new_label);
+
+        let remaining_proposals = self.label_to_proposals.get(old_label).unwrap_or_default().len();
+        let remaining_rfps = self.label_to_rfps.get(old_label).unwrap_or_default().len();
+        (remaining_proposals + remaining_rfps) as u32
+    }
+
+    /// Restricted rules and member permissions that match the label exactly are copied to the
+    /// new label, so the proposals and RFPs relabeled so far stay protected while the rename
+    /// takes several calls.
+    fn copy_label_access_control(&mut self, label: &str, new_label: &str) {
+        let rule = Rule::ExactMatch(label.to_string());
+        let new_rule = Rule::ExactMatch(new_label.to_string());
+        if let Some(metadata) = self.access_control.rules_list.rules.get(&rule).cloned() {
+            if !self.access_control.rules_list.rules.contains_key(&new_rule) {
+                self.set_restricted_rules_unchecked(RulesList {
+                    rules: HashMap::from([(new_rule.clone(), metadata)]),
+                });
+            }
+        }
+
+        let mut members: Vec<_> =
+            self.access_control.members_list.members.keys().cloned().collect();
+        members.sort();
+        for member in members {
+            let before = self.access_control.members_list.members[&member].clone();
+            let permissions = before.last_version().permissions;
+            let Some(actions) = permissions.get(&rule) else { continue };
+            if permissions
+                .get(&new_rule)
+                .map_or(false, |new_actions| new_actions.is_superset(actions))
+            {
+                continue;
+            }
+            let mut after = before.clone();
+            after.update_permissions(|permissions| {
+                let actions = permissions[&rule].clone();
+                permissions.entry(new_rule.clone()).or_default().extend(actions);
+            });
+            self.access_control.members_list.members.insert(member.clone(), after.clone());
+            self.add_access_control_audit_entry(AccessControlChange::EditMember {
+                member,
+                before,
+                after,
+            });
+        }
+    }
+
+    /// Drops the restricted rule and the member permissions of a label nothing uses anymore.
+    fn remove_label_access_control(&mut self, label: &str) {
+        let rule = Rule::ExactMatch(label.to_string());
+        if let Some(metadata) = self.access_control.rules_list.rules.get(&rule).cloned() {
+            self.add_access_control_audit_entry(AccessControlChange::UnsetRestrictedRules {
+                before: RulesList { rules: HashMap::from([(rule.clone(), metadata)]) },
+            });
+            self.access_control.rules_list.unset_restricted(vec![rule.clone()]);
+        }
+
+        let mut members: Vec<_> =
+            self.access_control.members_list.members.keys().cloned().collect();
+        members.sort();
+        for member in members {
+            let before = self.access_control.members_list.members[&member].clone();
+            if !before.last_version().permissions.contains_key(&rule) {
+                continue;
+            }
+            let mut after = before.clone();
+            after.update_permissions(|permissions| {
+                permissions.remove(&rule);
+            });
+            self.access_control.members_list.members.insert(member.clone(), after.clone());
+            self.add_access_control_audit_entry(AccessControlChange::EditMember {
+                member,
+                before,
+                after,
+            });
+        }
+    }
+}
+
+/// Renaming and merging labels relabel a limited number of proposals and RFPs per call and return
+/// how many are left. They should be called with the same arguments until they return zero, the
+/// global labels are only updated by the last call.
+#[near]
+impl Contract {
+    pub fn get_require_registered_proposal_labels(&self) -> bool {
+        self.require_registered_proposal_labels
+    }
+
+    pub fn set_require_registered_proposal_labels(&mut self, required: bool) {
+        require!(
+            self.is_allowed_to(&env::predecessor_account_id(), ActionType::ManageLabels, vec![]),
+            "Only the admin and moderators can change the proposal labels setting"
+        );
+        self.require_registered_proposal_labels = required;
+    }
+
+    /// Labels used by proposals that are not in the global labels.
+    pub fn get_unregistered_proposal_labels(&self) -> Vec<String> {
+        let mut res: Vec<_> = self
+            .label_to_proposals
+            .keys()
+            .filter(|label| !self.is_label_registered(label))
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// Moves the proposals from each unregistered label to the registered one it is mapped to.
+    /// Returns the unregistered labels that are still in use, so an empty map only lists them.
+    /// Like the label lifecycle methods, a call relabels a limited number of proposals, so it
+    /// should be repeated until none of the mapped labels is returned.
+    pub fn remap_unregistered_proposal_labels(
+        &mut self,
+        remap: HashMap<String, String>,
+    ) -> Vec<String> {
+        self.assert_can_manage_labels();
+        let mut remap: Vec<_> = remap.into_iter().collect();
+        remap.sort();
+        for (old_label, new_label) in &remap {
+            require!(
+                !self.is_label_registered(old_label),
+                format!("Label {} is registered", old_label)
+            );
+            require!(
+                self.is_label_registered(new_label),
+                format!("Label {} is not registered", new_label)
+            );
+        }
+        let mut limit = MAX_RELABELED_ENTRIES_PER_CALL;
+        for (old_label, new_label) in &remap {
+            limit -= self.replace_proposals_label_in_batch(old_label, Some(new_label), limit);
+        }
+        self.get_unregistered_proposal_labels()
+    }
+
+    /// Gives the registered label a new name, keeping its title and color, the restricted rule
+    /// and the member permissions that match it exactly. Other restricted rules that apply to the
+    /// label must apply to the new name too.
+    pub fn rename_label(&mut self, label: String, new_label: String) -> u32 {
+        self.assert_can_manage_labels();
+        require!(self.is_label_registered(&label), format!("Label {} is not registered", label));
+        require!(
+            !self.is_label_registered(&new_label),
+            format!("Label {} is already registered", new_label)
+        );
+        assert_labels_not_rules([&new_label]);
+        self.assert_restrictions_kept(&label, &new_label, true);
+
+        self.copy_label_access_control(&label, &new_label);
+        let remaining = self.replace_label_in_batch(&label, Some(&new_label));
+        if remaining == 0 {
+            self.remove_label_access_control(&label);
+            let label_info = (*self.global_labels_info).remove(&label).unwrap();
+            if self.deprecated_labels.remove(&label) {
+                self.deprecated_labels.insert(new_label.clone());
+            }
+            (*self.global_labels_info).insert(new_label, label_info);
+        }
+        remaining
+    }
+
+    /// Moves everything labeled with the label to another registered label and unregisters it.
+    /// Every restricted rule that applies to the label must apply to the other label too, so
+    /// that merging does not lift restrictions.
+    pub fn merge_labels(&mut self, label: String, into_label: String) -> u32 {
+        self.assert_can_manage_labels();
+        require!(label != into_label, "Cannot merge a label into itself");
+        require!(self.is_label_registered(&label), format!("Label {} is not registered", label));
+        require!(
+            self.is_label_registered(&into_label),
+            format!("Label {} is not registered", into_label)
+        );
+        self.assert_labels_not_deprecated([&into_label]);
+        self.assert_restrictions_kept(&label, &into_label, false);
+
+        let remaining = self.replace_label_in_batch(&label, Some(&into_label));
+        if remaining == 0 {
+            self.deprecated_labels.remove(&label);
+            (*self.global_labels_info).remove(&label);
+        }
+        remaining
+    }
+
+    pub fn get_deprecated_labels(&self) -> Vec<String> {
+        let mut res: Vec<_> = self.deprecated_labels.iter().cloned().collect();
+        res.sort();
+        res
+    }
+
+    /// Keeps the label registered and on the proposals and RFPs that have it, but it can't be
+    /// added to any other proposal or RFP.
+    pub fn deprecate_label(&mut self, label: String) {
+        self.assert_can_manage_labels();
+        require!(self.is_label_registered(&label), format!("Label {} is not registered", label));
+        require!(
+            self.deprecated_labels.insert(label.clone()),
+            format!("Label {} is already deprecated", label)
+        );
+    }
+}
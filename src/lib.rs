@@ -3,23 +3,37 @@ pub mod changelog;
 pub mod common;
 pub mod community;
 pub mod debug;
+pub mod diff;
+pub mod labels;
 pub mod migrations;
 mod notify;
+pub mod ownership;
 pub mod proposal;
 pub mod rfp;
 pub mod stats;
 pub mod str_serializers;
 pub mod web4;
 
+use crate::access_control::approvals::{SensitiveActionKind, SensitiveActions};
+use crate::access_control::audit::AccessControlAuditEntry;
 use crate::access_control::members::ActionType;
 use crate::access_control::members::Member;
-use crate::access_control::AccessControl;
+use crate::access_control::{AccessControl, DEFAULT_MODERATORS_TEAM};
 use changelog::*;
+use community::admins::CommunityAdminInvitation;
+use community::archive::CommunityArchive;
+use community::members::CommunityMembership;
+use community::rename::CommunityAlias;
 use community::*;
 
 use common::*;
-use proposal::timeline::{TimelineStatus, TimelineStatusV1, VersionedTimelineStatus};
+use diff::FieldChange;
+use proposal::timeline::{
+    TimelineStatus, TimelineStatusV1, TimelineTransition, VersionedTimelineStatus,
+};
+use proposal::workflow::ProposalWorkflow;
 use proposal::*;
+use rfp::evaluation::RFPEvaluation;
 use rfp::{
     RFPId, RFPSnapshot, TimelineStatus as RFPTimelineStatus, VersionedRFP, VersionedRFPBody, RFP,
 };
@@ -27,14 +41,15 @@ use rfp::{
 use devhub_common::{social_db_contract, SetReturnType};
 
 use near_sdk::borsh::BorshDeserialize;
-use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, Vector};
+use near_sdk::json_types::U64;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::{json, Number, Value};
 use near_sdk::store::Lazy;
 use near_sdk::{env, near, require, AccountId, NearSchema, PanicOnDefault, Promise};
 use web4::types::{Web4Request, Web4Response};
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 
 type PostId = u64;
@@ -42,6 +57,25 @@ type PostId = u64;
 /// An imaginary top post representing the landing page.
 const ROOT_POST_ID: u64 = u64::MAX;
 
+const DEFAULT_PROPOSALS_PAGE_LIMIT: u32 = 20;
+const MAX_PROPOSALS_SCANNED_PER_PAGE: usize = 500;
+
+/// The `count` smallest ids from `from_index` on, in order, without sorting the whole set.
+fn smallest_ids_from(
+    ids: HashSet<ProposalId>,
+    from_index: ProposalId,
+    count: usize,
+) -> Vec<ProposalId> {
+    let mut smallest = BinaryHeap::with_capacity(count + 1);
+    for id in ids.into_iter().filter(|id| *id >= from_index) {
+        smallest.push(id);
+        if smallest.len() > count {
+            smallest.pop();
+        }
+    }
+    smallest.into_sorted_vec()
+}
+
 #[near(contract_state)]
 #[derive(PanicOnDefault)]
 pub struct Contract {
@@ -62,13 +96,31 @@ pub struct Contract {
     pub featured_communities: Vec<FeaturedCommunity>,
     pub available_addons: UnorderedMap<AddOnId, AddOn>,
     pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+    pub archived_communities: UnorderedMap<CommunityHandle, CommunityArchive>,
+    pub community_aliases: UnorderedMap<CommunityHandle, CommunityAlias>,
+    pub tag_to_communities: UnorderedMap<String, HashSet<CommunityHandle>>,
+    pub community_search_index: TreeMap<String, HashSet<CommunityHandle>>,
+    pub deprecated_labels: HashSet<String>,
 }
 
 #[near]
 impl Contract {
     #[init]
     pub fn new() -> Self {
-        migrations::state_version_write(&migrations::StateVersion::V12);
+        migrations::state_version_write(&migrations::StateVersion::V27);
 
         let mut contract = Self {
             posts: Vector::new(StorageKey::Posts),
@@ -88,6 +140,23 @@ impl Contract {
             featured_communities: Vec::new(),
             available_addons: UnorderedMap::new(StorageKey::AddOns),
             change_log: VecDeque::new(),
+            proposal_workflow: Lazy::new(StorageKey::ProposalWorkflow, ProposalWorkflow::default()),
+            proposal_timeline_history: LookupMap::new(StorageKey::ProposalTimelineHistory),
+            rfp_evaluations: LookupMap::new(StorageKey::RFPEvaluations),
+            require_registered_proposal_labels: false,
+            access_control_audit_log: Vector::new(StorageKey::AccessControlAuditLog),
+            sensitive_actions: SensitiveActions::default(),
+            moderators_team: DEFAULT_MODERATORS_TEAM.to_string(),
+            owner: env::current_account_id(),
+            proposed_owner: None,
+            community_members: LookupMap::new(StorageKey::CommunityMembers),
+            account_communities: LookupMap::new(StorageKey::AccountCommunities),
+            community_admin_invitations: LookupMap::new(StorageKey::CommunityAdminInvitations),
+            archived_communities: UnorderedMap::new(StorageKey::ArchivedCommunities),
+            community_aliases: UnorderedMap::new(StorageKey::CommunityAliases),
+            tag_to_communities: UnorderedMap::new(StorageKey::TagToCommunities),
+            community_search_index: TreeMap::new(StorageKey::CommunitySearchIndex),
+            deprecated_labels: HashSet::new(),
         };
 
         contract.post_to_children.insert(&ROOT_POST_ID, &Vec::new());
@@ -102,22 +171,121 @@ impl Contract {
         }
     }
 
+    /// Scans proposals in id order starting at `from_index` and returns up to `limit` of them that
+    /// match `filter`. Label and author filters are served from their indexes. A single call scans
+    /// at most `MAX_PROPOSALS_SCANNED_PER_PAGE` proposals, so a page can be shorter than `limit`
+    /// while `next_index` is still set.
+    /// A `limit` of zero is treated as one, so that every page makes progress.
+    pub fn get_proposals_paged(
+        &self,
+        from_index: Option<ProposalId>,
+        limit: Option<u32>,
+        filter: Option<ProposalFilter>,
+    ) -> ProposalsPage {
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_PROPOSALS_PAGE_LIMIT).max(1) as usize;
+        let filter = filter.unwrap_or_default();
+
+        // One candidate past the scan limit tells where the next page starts.
+        let max_candidates = MAX_PROPOSALS_SCANNED_PER_PAGE + 1;
+        let indexed_ids = [
+            filter
+                .label
+                .as_ref()
+                .map(|label| self.label_to_proposals.get(label).unwrap_or_default()),
+            filter
+                .author_id
+                .as_ref()
+                .map(|author_id| self.author_proposals.get(author_id).unwrap_or_default()),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(|ids| ids.len());
+        let candidates: Vec<ProposalId> = if let Some(ids) = indexed_ids {
+            smallest_ids_from(ids, from_index, max_candidates)
+        } else {
+            let len: ProposalId = self.proposals.len().try_into().unwrap();
+            (from_index.min(len)..len).take(max_candidates).collect()
+        };
+
+        let mut proposals = vec![];
+        let mut next_index = None;
+        for (scanned, id) in candidates.iter().enumerate() {
+            if proposals.len() >= limit || scanned >= MAX_PROPOSALS_SCANNED_PER_PAGE {
+                next_index = Some(*id);
+                break;
+            }
+            if let Some(versioned_proposal) = self.proposals.get((*id).into()) {
+                if filter.matches(&versioned_proposal.clone().into()) {
+                    proposals.push(versioned_proposal);
+                }
+            }
+        }
+
+        ProposalsPage { proposals, next_index }
+    }
+
     pub fn get_proposal(&self, proposal_id: ProposalId) -> VersionedProposal {
         self.proposals
             .get(proposal_id.into())
             .unwrap_or_else(|| panic!("Proposal id {} not found", proposal_id))
     }
 
+    pub fn get_proposal_timeline_history(&self, id: ProposalId) -> Vec<TimelineTransition> {
+        require!(id < self.proposals.len() as ProposalId, format!("Proposal id {} not found", id));
+        self.proposal_timeline_history.get(&id).unwrap_or_default()
+    }
+
+    /// Fields changed between two snapshots of the proposal. Snapshots are indexed from the
+    /// oldest one in `snapshot_history`, the index right after it is the current snapshot.
+    pub fn get_proposal_diff(
+        &self,
+        id: ProposalId,
+        from_snapshot_index: u32,
+        to_snapshot_index: u32,
+    ) -> Vec<FieldChange> {
+        let proposal: Proposal = self.get_proposal(id).into();
+        diff::diff_proposal_snapshots(
+            proposal.snapshot_at(from_snapshot_index),
+            proposal.snapshot_at(to_snapshot_index),
+        )
+    }
+
     pub fn get_all_proposal_ids(&self) -> Vec<ProposalId> {
         (0..self.proposals.len().try_into().unwrap()).collect()
     }
 
+    /// RFPs keep the shape they had before the full snapshots were stored, with the block heights
+    /// of the previous snapshots. `get_rfp_at_block_height` returns the snapshots themselves.
     pub fn get_rfps(&self) -> Vec<VersionedRFP> {
-        self.rfps.to_vec()
+        self.rfps.iter().map(|rfp| VersionedRFP::V0(RFP::from(rfp).into())).collect()
     }
 
     pub fn get_rfp(&self, rfp_id: RFPId) -> VersionedRFP {
-        self.rfps.get(rfp_id.into()).unwrap_or_else(|| panic!("RFP id {} not found", rfp_id))
+        VersionedRFP::V0(self.load_rfp(rfp_id).into())
+    }
+
+    /// The RFP as it was at the given block height, `None` if it did not exist yet.
+    pub fn get_rfp_at_block_height(
+        &self,
+        rfp_id: RFPId,
+        block_height: U64,
+    ) -> Option<VersionedRFP> {
+        self.load_rfp(rfp_id).at_block_height(block_height.0).map(Into::into)
+    }
+
+    /// Fields changed between two snapshots of the RFP, indexed the same way as for proposals.
+    pub fn get_rfp_diff(
+        &self,
+        id: RFPId,
+        from_snapshot_index: u32,
+        to_snapshot_index: u32,
+    ) -> Vec<FieldChange> {
+        let rfp = self.load_rfp(id);
+        diff::diff_rfp_snapshots(
+            rfp.snapshot_at(from_snapshot_index),
+            rfp.snapshot_at(to_snapshot_index),
+        )
     }
 
     pub fn get_all_rfp_ids(&self) -> Vec<RFPId> {
@@ -161,8 +329,11 @@ impl Contract {
             require!(labels.is_empty(), "Cannot add custom labels to this proposal. It inherits labels from the linked RFP. You should not add any labels to this proposal manually");
         }
 
+        labels::assert_labels_not_rules(&labels);
+        self.assert_labels_not_deprecated(&labels);
         let labels = self.update_and_check_rfp_link(id, body.clone(), None, labels);
 
+        self.assert_proposal_labels_registered(&labels);
         require!(
             self.is_allowed_to_use_labels(
                 Some(editor_id.clone()),
@@ -173,6 +344,12 @@ impl Contract {
 
         require!(self.proposal_categories.contains(&proposal_body.category), "Unknown category");
 
+        require!(!proposal_body.milestones.is_empty(), "Proposal must have at least one milestone");
+        require!(
+            proposal_body.milestones.iter().all(|milestone| milestone.status.is_pending()),
+            "Cannot create proposal with milestones which are already delivered or funded"
+        );
+
         let timeline = proposal_body.timeline.clone().latest_version();
 
         require!(
@@ -229,12 +406,18 @@ impl Contract {
             rfp_body.timeline.is_accepting_submissions(),
             "Cannot create proposal which is not in a Accepting Submissions state"
         );
+        require!(
+            rfp_body.selected_proposals.is_empty(),
+            "Cannot create an RFP with selected proposals"
+        );
 
         for label in &labels {
             require!(
                 self.global_labels_info.get().get(label).is_some(),
                 format!("Label {} is not registered", label)
             );
+            labels::assert_labels_not_rules([label]);
+            self.assert_labels_not_deprecated([label]);
 
             let mut other_rfps = self.label_to_rfps.get(label).unwrap_or_default();
             other_rfps.insert(id);
@@ -254,6 +437,7 @@ impl Contract {
                 linked_proposals: HashSet::new(),
             },
             snapshot_history: vec![],
+            legacy_snapshot_history: vec![],
         };
 
         proposal::repost::publish_to_socialdb_feed(
@@ -340,7 +524,7 @@ impl Contract {
             .into();
         let editor = editor.unwrap_or_else(env::predecessor_account_id);
         // First check for simple cases.
-        if editor == env::current_account_id() || editor == proposal.author_id {
+        if self.is_owner(&editor) || editor == proposal.author_id {
             return true;
         }
 
@@ -352,13 +536,13 @@ impl Contract {
     }
 
     pub fn is_allowed_to_write_rfps(&self, editor: AccountId) -> bool {
-        editor == env::current_account_id() || self.has_moderator(editor)
+        self.is_allowed_to(&editor, ActionType::WriteRfp, vec![])
     }
 
     pub fn is_allowed_to_use_labels(&self, editor: Option<AccountId>, labels: Vec<String>) -> bool {
         let editor = editor.unwrap_or_else(env::predecessor_account_id);
         // First check for simple cases.
-        if editor == env::current_account_id() {
+        if self.is_owner(&editor) {
             return true;
         }
         let restricted_labels = self.access_control.rules_list.find_restricted(&labels);
@@ -445,6 +629,7 @@ impl Contract {
         new_community.validate();
         new_community.set_default_admin();
         self.communities.insert(&new_community.handle, &new_community);
+        self.add_community_to_indexes(&new_community);
 
         ext_devhub_community_factory::ext(get_devhub_community_factory())
             .with_unused_gas_weight(1)
@@ -471,16 +656,19 @@ impl Contract {
         &mut self,
         id: ProposalId,
         timeline: TimelineStatusV1,
+        reason: Option<String>,
     ) -> ProposalId {
+        let timeline: VersionedTimelineStatus = timeline.into();
+        self.record_legacy_payouts(id, &timeline);
         let proposal: Proposal = self
             .proposals
             .get(id.into())
             .unwrap_or_else(|| panic!("Proposal id {} not found", id))
             .into();
         let mut body = proposal.snapshot.body.latest_version();
-        body.timeline = timeline.into();
+        body.set_timeline(timeline);
 
-        self.edit_proposal_internal(id, body.into(), proposal.snapshot.labels)
+        self.edit_proposal_timeline_internal(id, body.into(), proposal.snapshot.labels, reason)
     }
 
     #[payable]
@@ -488,17 +676,31 @@ impl Contract {
         &mut self,
         id: ProposalId,
         timeline: VersionedTimelineStatus,
+        reason: Option<String>,
     ) -> ProposalId {
         near_sdk::log!("edit_proposal_versioned_timeline");
+        self.record_legacy_payouts(id, &timeline);
         let proposal: Proposal = self
             .proposals
             .get(id.into())
             .unwrap_or_else(|| panic!("Proposal id {} not found", id))
             .into();
         let mut body = proposal.snapshot.body.latest_version();
-        body.timeline = timeline.into();
+        body.set_timeline(timeline);
 
-        self.edit_proposal_internal(id, body.into(), proposal.snapshot.labels)
+        self.edit_proposal_timeline_internal(id, body.into(), proposal.snapshot.labels, reason)
+    }
+
+    /// Lets the supervisor mark a single milestone as delivered or funded while the proposal
+    /// stays open.
+    #[payable]
+    pub fn edit_proposal_milestone(
+        &mut self,
+        id: ProposalId,
+        milestone_index: u32,
+        status: MilestoneStatus,
+    ) -> ProposalId {
+        self.edit_proposal_milestone_internal(id, milestone_index, status)
     }
 
     #[payable]
@@ -534,6 +736,7 @@ impl Contract {
         id: RFPId,
         proposals_to_cancel: Vec<ProposalId>,
         proposals_to_unlink: Vec<ProposalId>,
+        reason: Option<String>,
     ) -> RFPId {
         for proposal_id in proposals_to_cancel {
             let proposal: Proposal = self.get_proposal(proposal_id).into();
@@ -542,6 +745,7 @@ impl Contract {
             self.edit_proposal_versioned_timeline(
                 proposal_id,
                 TimelineStatus::Cancelled(review_status).into(),
+                reason.clone(),
             );
         }
 
@@ -554,13 +758,31 @@ impl Contract {
 
     #[payable]
     pub fn edit_rfp_timeline(&mut self, id: RFPId, timeline: RFPTimelineStatus) -> ProposalId {
-        let rfp: RFP = self.get_rfp(id).into();
+        let rfp = self.load_rfp(id);
         let mut body = rfp.snapshot.body.latest_version();
         body.timeline = timeline;
 
         self.edit_rfp_internal(id, body.into(), rfp.snapshot.labels)
     }
 
+    /// Moves the RFP to evaluation once its submission deadline has passed. Anyone can call it.
+    #[payable]
+    pub fn close_rfp_submissions(&mut self, id: RFPId) -> RFPId {
+        let rfp = self.load_rfp(id);
+        let mut body = rfp.snapshot.body.latest_version();
+        require!(
+            body.timeline.is_accepting_submissions(),
+            format!("The RFP {} is not accepting submissions", id)
+        );
+        require!(
+            body.is_submission_deadline_passed(),
+            format!("The submission deadline of the RFP {} has not passed yet", id)
+        );
+        body.timeline = RFPTimelineStatus::Evaluation;
+
+        self.edit_rfp_unchecked(id, body.into(), rfp.snapshot.labels)
+    }
+
     pub fn get_allowed_categories(&self) -> Vec<String> {
         self.proposal_categories.clone()
     }
@@ -587,30 +809,34 @@ impl Contract {
     pub fn set_global_labels(&mut self, labels: Vec<LabelInfoExtended>) {
         let editor_id = env::predecessor_account_id();
         require!(
-            self.has_moderator(editor_id.clone()) || editor_id.clone() == env::current_account_id(),
+            self.is_allowed_to(&editor_id, ActionType::ManageLabels, vec![]),
             "Only the admin and moderators can set labels"
         );
 
+        labels::assert_labels_not_rules(labels.iter().map(|label| &label.value));
         self.global_labels_info.clear();
 
         for label in labels {
             let label_info = LabelInfo { title: label.title, color: label.color };
             (*self.global_labels_info).insert(label.value, label_info);
         }
+        let global_labels_info = &self.global_labels_info;
+        self.deprecated_labels.retain(|label| global_labels_info.contains_key(label));
     }
 
     #[payable]
     pub fn set_allowed_categories(&mut self, new_categories: Vec<String>) {
         let editor_id = env::predecessor_account_id();
         require!(
-            self.has_moderator(editor_id.clone()) || editor_id.clone() == env::current_account_id(),
+            self.is_allowed_to(&editor_id, ActionType::ManageCategories, vec![]),
             "Only the admin and moderators can set categories"
         );
         self.proposal_categories = new_categories;
     }
 
+    /// Old handles of renamed communities resolve to the community under its new handle.
     pub fn get_community(&self, handle: CommunityHandle) -> Option<Community> {
-        self.communities.get(&handle)
+        self.communities.get(&self.resolve_community_handle(&handle)?)
     }
 
     pub fn get_community_metadata(&self, handle: CommunityHandle) -> Option<CommunityMetadata> {
@@ -631,21 +857,22 @@ impl Contract {
         account_id: AccountId,
         community_handle: CommunityHandle,
     ) -> CommunityPermissions {
-        let community = self.get_community(community_handle.to_owned()).expect(
+        let community = self.communities.get(&community_handle).expect(
             format!("Community with handle `{}` does not exist", community_handle).as_str(),
         );
 
         CommunityPermissions {
             can_configure: community.admins.contains(&account_id)
-                || self.has_moderator(account_id.to_owned()),
+                || self.is_allowed_to(&account_id, ActionType::ModerateCommunities, vec![]),
 
-            can_delete: self.has_moderator(account_id),
+            can_delete: self.is_allowed_to(&account_id, ActionType::ModerateCommunities, vec![]),
         }
     }
 
     pub fn get_all_communities_metadata(&self) -> Vec<CommunityMetadata> {
         self.communities
             .iter()
+            .filter(|(handle, _)| !self.is_community_archived(handle.clone()))
             .map(|(handle, community)| CommunityMetadata {
                 admins: community.admins,
                 handle,
@@ -669,9 +896,7 @@ impl Contract {
 
     // Only the contract admin and DevHub moderators
     pub fn create_addon(&mut self, addon: AddOn) {
-        if !self.has_moderator(env::predecessor_account_id())
-            && env::predecessor_account_id() != env::current_account_id()
-        {
+        if !self.is_allowed_to(&env::predecessor_account_id(), ActionType::ManageAddons, vec![]) {
             panic!("Only the admin and moderators can create new add-ons");
         }
         if self.get_addon(addon.id.to_owned()).is_some() {
@@ -684,9 +909,7 @@ impl Contract {
     // ONLY FOR TESTING
     pub fn delete_addon(&mut self, id: AddOnId) {
         // Also delete from communities
-        if !self.has_moderator(env::predecessor_account_id())
-            && env::predecessor_account_id() != env::current_account_id()
-        {
+        if !self.is_allowed_to(&env::predecessor_account_id(), ActionType::ManageAddons, vec![]) {
             panic!("Only the admin and moderators can delete add-ons");
         }
         let addon = self
@@ -698,9 +921,7 @@ impl Contract {
     }
 
     pub fn update_addon(&mut self, addon: AddOn) {
-        if !self.has_moderator(env::predecessor_account_id())
-            && env::predecessor_account_id() != env::current_account_id()
-        {
+        if !self.is_allowed_to(&env::predecessor_account_id(), ActionType::ManageAddons, vec![]) {
             panic!("Only the admin and moderators can edit add-ons");
         }
         self.available_addons.insert(&addon.id.clone(), &addon);
@@ -719,7 +940,8 @@ impl Contract {
         addons: Vec<CommunityAddOn>,
     ) -> Promise {
         let mut community = self
-            .get_community(handle.clone())
+            .communities
+            .get(&handle)
             .expect(format!("Community not found with handle `{}`", handle).as_str());
         community.addons = addons;
         self.update_community(handle, community)
@@ -732,7 +954,8 @@ impl Contract {
         community_addon: CommunityAddOn,
     ) -> Promise {
         let mut community = self
-            .get_community(handle.clone())
+            .communities
+            .get(&handle)
             .expect(format!("Community not found with handle `{}`", handle).as_str());
         if let Some(existing_addon) =
             community.addons.iter_mut().find(|current| current.id == community_addon.id)
@@ -745,11 +968,12 @@ impl Contract {
     }
 
     fn get_editable_community(&self, handle: &CommunityHandle) -> Option<Community> {
+        self.assert_community_not_archived(handle);
         if self
             .get_account_community_permissions(env::predecessor_account_id(), handle.to_owned())
             .can_configure
         {
-            return self.get_community(handle.to_owned());
+            return self.communities.get(handle);
         } else {
             return None;
         };
@@ -760,16 +984,31 @@ impl Contract {
         handle: CommunityHandle,
         #[allow(unused_mut)] mut community: Community,
     ) -> Promise {
-        let _ = self
+        let current = self
             .get_editable_community(&handle)
             .expect("Only community admins and hub moderators can configure communities");
 
         community.validate();
-        community.set_default_admin();
+        // Admins are managed with the invitations, an empty list keeps the current admins.
+        require!(
+            community.admins.is_empty() || community.admins == current.admins,
+            "Community admins can only be changed with invite_community_admin, remove_community_admin and transfer_community_ownership"
+        );
+        community.admins = current.admins.clone();
 
-        require!(community.handle == handle, "Community handle cannot be changed");
+        require!(
+            community.handle == handle,
+            "Community handle cannot be changed, use rename_community"
+        );
         require!(env::prepaid_gas() >= UPDATE_COMMUNITY_GAS, "Require at least 30 Tgas");
         self.communities.insert(&handle, &community);
+        self.remove_community_from_indexes(&current);
+        self.add_community_to_indexes(&community);
+        self.set_community_profiles(&community)
+    }
+
+    /// Writes the SocialDB profiles of the community account and its discussions account.
+    pub(crate) fn set_community_profiles(&self, community: &Community) -> Promise {
         let community_page_link =
             format!("/devhub.near/widget/app?page=community&handle={}", community.handle);
         social_db_contract().with_unused_gas_weight(1).set(json!({
@@ -838,6 +1077,7 @@ impl Contract {
     }
 
     pub fn create_discussion(&mut self, handle: CommunityHandle, block_height: Number) -> Promise {
+        self.assert_community_not_archived(&handle);
         require!(env::prepaid_gas() >= CREATE_DISCUSSION_GAS, "Require at least 30 Tgas");
 
         let post_initiator = env::predecessor_account_id();
@@ -853,33 +1093,80 @@ impl Contract {
         )
     }
 
-    pub fn delete_community(&mut self, handle: CommunityHandle) -> Promise {
+    /// Deletes an archived community for good, together with its account, the accounts of its old
+    /// handles that were not released yet, and everything stored under them. `confirm_handle`
+    /// must repeat the handle.
+    pub fn delete_community(
+        &mut self,
+        handle: CommunityHandle,
+        confirm_handle: CommunityHandle,
+    ) -> Promise {
+        require!(confirm_handle == handle, "The confirmation does not match the community handle");
         require!(
-            self.has_moderator(env::predecessor_account_id()),
+            self.is_allowed_to(
+                &env::predecessor_account_id(),
+                ActionType::ModerateCommunities,
+                vec![]
+            ),
             "Only moderators can delete community"
         );
+        self.assert_no_approval_required(SensitiveActionKind::DeleteCommunity);
+        self.delete_community_unchecked(handle)
+    }
 
+    pub(crate) fn delete_community_unchecked(&mut self, handle: CommunityHandle) -> Promise {
         let community = self
-            .get_community(handle.clone())
+            .communities
+            .get(&handle)
             .expect(&format!("Community with handle `{}` does not exist", handle));
+        require!(
+            self.is_community_archived(handle.clone()),
+            "Only archived communities can be deleted, archive the community first"
+        );
 
+        let unreleased_aliases = self.remove_community_aliases(&community.handle);
         self.communities.remove(&community.handle);
+        self.archived_communities.remove(&community.handle);
+        self.remove_community_from_indexes(&community);
+        self.remove_community_teams(&community.handle);
+        self.remove_community_members(&community.handle);
+        self.remove_community_admin_invitations(&community.handle);
 
         require!(env::prepaid_gas() >= DELETE_COMMUNITY_GAS, "Require at least 30 Tgas");
-        ext_devhub_community::ext(get_devhub_community_account(&community.handle).parse().unwrap())
+        unreleased_aliases.iter().fold(
+            ext_devhub_community::ext(
+                get_devhub_community_account(&community.handle).parse().unwrap(),
+            )
             .with_unused_gas_weight(1)
-            .destroy()
+            .destroy(),
+            |promise, alias| {
+                promise.and(
+                    ext_devhub_community::ext(get_devhub_community_account(alias).parse().unwrap())
+                        .with_unused_gas_weight(1)
+                        .destroy(),
+                )
+            },
+        )
     }
 
     pub fn set_featured_communities(&mut self, handles: Vec<CommunityHandle>) {
         require!(
-            self.has_moderator(env::predecessor_account_id()),
+            self.is_allowed_to(
+                &env::predecessor_account_id(),
+                ActionType::ModerateCommunities,
+                vec![]
+            ),
             "Only moderators can add featured communities"
         );
+        self.assert_no_approval_required(SensitiveActionKind::SetFeaturedCommunities);
+        self.set_featured_communities_unchecked(handles)
+    }
 
+    pub(crate) fn set_featured_communities_unchecked(&mut self, handles: Vec<CommunityHandle>) {
         // Check if every handle corresponds to an existing community
         for handle in &handles {
             require!(self.communities.get(&handle).is_some(), "Community does not exist.");
+            self.assert_community_not_archived(handle);
         }
 
         // Replace the existing featured communities with the new ones
@@ -890,13 +1177,14 @@ impl Contract {
     pub fn get_featured_communities(&self) -> Vec<Community> {
         self.featured_communities
             .iter()
+            .filter(|fc| !self.is_community_archived(fc.handle.clone()))
             .filter_map(|fc| self.get_community(fc.handle.clone()))
             .collect()
     }
 
     fn get_moderators(&self) -> HashSet<AccountId> {
         let mut moderators: HashSet<AccountId> = HashSet::new();
-        for m in self.access_control.members_list.get_moderators() {
+        for m in self.access_control.members_list.get_moderators(&self.moderators_team) {
             if let Member::Account(account_id) = m {
                 moderators.insert(account_id.clone());
             }
@@ -906,7 +1194,7 @@ impl Contract {
     }
 
     pub fn has_moderator(&self, account_id: AccountId) -> bool {
-        let moderators = self.access_control.members_list.get_moderators();
+        let moderators = self.access_control.members_list.get_moderators(&self.moderators_team);
         moderators.contains(&Member::Account(account_id))
     }
 
@@ -916,10 +1204,7 @@ impl Contract {
 
     pub fn set_social_db_profile_description(&self, description: String) -> Promise {
         let editor = env::predecessor_account_id();
-        require!(
-            editor == env::current_account_id() || self.has_moderator(editor),
-            "Permission denied"
-        );
+        require!(self.is_owner(&editor) || self.has_moderator(editor), "Permission denied");
         social_db_contract()
             .with_static_gas(env::prepaid_gas().saturating_div(3))
             .with_attached_deposit(env::attached_deposit())
@@ -954,17 +1239,37 @@ pub struct BlockHeightCallbackRetValue {
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
-    use crate::community::AddOn;
-
-    use crate::{ProposalBodyV0, VersionedProposalBody};
+    use crate::community::members::{CommunityMember, CommunityRole};
+    use crate::community::{AddOn, Community, CommunityMetadata};
+
+    use crate::access_control::approvals::{ApprovalPolicy, SensitiveAction, SensitiveActionKind};
+    use crate::access_control::audit::AccessControlChange;
+    use crate::access_control::members::{
+        ActionType, Member, MemberMetadata, VersionedMemberMetadata,
+    };
+    use crate::access_control::rules::{Rule, RuleMetadata, RulesList};
+    use crate::diff::FieldChange;
+    use crate::proposal::timeline::{
+        TimelineStatusKind, TimelineTransition, VersionedTimelineStatus,
+    };
+    use crate::proposal::workflow::{ProposalRole, ProposalTransition, ProposalWorkflow};
+    use crate::rfp::evaluation::{EvaluationCriterion, ProposalRanking};
+    use crate::rfp::{
+        RFPBodyV0, RFPSnapshot, SelectedProposal, TimelineStatus as RFPTimelineStatus,
+        VersionedRFP, RFP, RFPV0,
+    };
+    use crate::{
+        MilestoneStatus, Proposal, ProposalBodyV0, ProposalBodyV2, ProposalBodyV3, ProposalFilter,
+        ProposalId, ProposalSnapshot, VersionedProposalBody,
+    };
 
     use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
     use near_sdk::{testing_env, VMContext};
     use serde_json::json;
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
     use std::convert::TryInto;
 
-    use super::Contract;
+    use super::{Contract, LabelInfoExtended};
 
     fn get_context(is_view: bool) -> VMContext {
         get_context_with_signer(is_view, "bob.near".to_string())
@@ -1027,6 +1332,1960 @@ mod tests {
         }
     }
 
+    fn fake_proposal(id: ProposalId, category: &str, labels: &[&str]) -> Proposal {
+        let body: ProposalBodyV0 = near_sdk::serde_json::from_value(json!({
+            "name": format!("proposal {}", id),
+            "description": "description",
+            "category": category,
+            "summary": "sum",
+            "linked_proposals": [],
+            "requested_sponsorship_usd_amount": "1000",
+            "requested_sponsorship_paid_in_currency": "USDT",
+            "receiver_account": "polyprogrammist.near",
+            "supervisor": "frol.near",
+            "requested_sponsor": "neardevdao.near",
+            "timeline": {"status": "DRAFT"}
+        }))
+        .unwrap();
+        Proposal {
+            id,
+            author_id: "bob.near".parse().unwrap(),
+            social_db_post_block_height: 0,
+            snapshot: ProposalSnapshot {
+                editor_id: "bob.near".parse().unwrap(),
+                timestamp: id.into(),
+                labels: labels.iter().map(|label| label.to_string()).collect(),
+                body: VersionedProposalBody::V0(body),
+            },
+            snapshot_history: vec![],
+        }
+    }
+
+    #[test]
+    pub fn test_get_proposals_paged() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        for id in 0..5 {
+            let category = if id % 2 == 0 { "Marketing" } else { "Events" };
+            let proposal = fake_proposal(id, category, &["test"]);
+            contract.proposals.push(&proposal.into());
+        }
+        contract.label_to_proposals.insert(&"test".to_string(), &(0..5).collect());
+
+        let page = contract.get_proposals_paged(None, Some(2), None);
+        assert_eq!(page.proposals.len(), 2);
+        assert_eq!(page.next_index, Some(2));
+
+        let page = contract.get_proposals_paged(Some(4), Some(2), None);
+        assert_eq!(page.proposals.len(), 1);
+        assert_eq!(page.next_index, None);
+
+        let filter = ProposalFilter {
+            category: Some("Marketing".to_string()),
+            label: Some("test".to_string()),
+            from_timestamp: Some(1.into()),
+            ..Default::default()
+        };
+        let page = contract.get_proposals_paged(None, Some(10), Some(filter));
+        let ids: Vec<ProposalId> =
+            page.proposals.into_iter().map(|p| Proposal::from(p).id).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(page.next_index, None);
+    }
+
+    #[test]
+    pub fn test_get_proposals_paged_with_zero_limit() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        for id in 0..3 {
+            contract.proposals.push(&fake_proposal(id, "Marketing", &["test"]).into());
+        }
+        contract.label_to_proposals.insert(&"test".to_string(), &(0..3).collect());
+        contract.author_proposals.insert(&"bob.near".parse().unwrap(), &(0..3).collect());
+
+        let page = contract.get_proposals_paged(Some(1), Some(0), None);
+        assert_eq!(page.proposals.len(), 1);
+        assert_eq!(page.next_index, Some(2));
+
+        let filter = ProposalFilter {
+            label: Some("test".to_string()),
+            author_id: Some("bob.near".parse().unwrap()),
+            ..Default::default()
+        };
+        let page = contract.get_proposals_paged(Some(1), Some(0), Some(filter));
+        assert_eq!(page.proposals.len(), 1);
+        assert_eq!(page.next_index, Some(2));
+    }
+
+    #[test]
+    pub fn test_edit_proposal_milestone() {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("frol.near".parse().unwrap())
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let body: ProposalBodyV3 = near_sdk::serde_json::from_value(json!({
+            "name": "milestones",
+            "description": "description",
+            "category": "Marketing",
+            "summary": "sum",
+            "linked_proposals": [],
+            "requested_sponsorship_paid_in_currency": "USDT",
+            "receiver_account": "polyprogrammist.near",
+            "supervisor": "frol.near",
+            "requested_sponsor": "neardevdao.near",
+            "timeline": {"timeline_version": "V2", "status": "APPROVED", "sponsor_requested_review": true, "reviewer_completed_attestation": true, "kyc_verified": true},
+            "linked_rfp": null,
+            "milestones": [
+                {"description": "first", "usd_amount": "1000", "due_date": "1", "status": {"status": "PENDING"}},
+                {"description": "second", "usd_amount": "2000", "due_date": "2", "status": {"status": "PENDING"}}
+            ]
+        }))
+        .unwrap();
+        let mut proposal = fake_proposal(0, "Marketing", &[]);
+        proposal.snapshot.body = VersionedProposalBody::V3(body);
+        contract.proposals.push(&proposal.into());
+
+        contract.edit_proposal_milestone(
+            0,
+            0,
+            MilestoneStatus::Funded { payouts: vec!["https://nearblocks.io/txns/1".to_string()] },
+        );
+
+        let proposal: Proposal = contract.get_proposal(0).into();
+        let body = proposal.snapshot.body.latest_version();
+        assert!(body.milestones[0].status.is_funded());
+        assert!(body.milestones[1].status.is_pending());
+        assert!(body.timeline.latest_version().was_approved());
+        assert_eq!(proposal.snapshot_history.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The milestone cannot be moved back to a previous status")]
+    pub fn test_edit_proposal_milestone_backwards() {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("frol.near".parse().unwrap())
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let mut proposal = fake_proposal(0, "Marketing", &[]);
+        let mut body = proposal.snapshot.body.latest_version();
+        body.set_timeline(
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V2", "status": "APPROVED", "sponsor_requested_review": true, "reviewer_completed_attestation": true, "kyc_verified": true}))
+                .unwrap(),
+        );
+        proposal.snapshot.body = body.into();
+        contract.proposals.push(&proposal.into());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("devhub.near".parse().unwrap())
+            .build());
+        contract.edit_proposal_versioned_timeline(
+            0,
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V1", "status": "FUNDED", "trustees_released_payment": true, "kyc_verified": true, "test_transaction_sent": true, "request_for_trustees_created": true, "sponsor_requested_review": true, "reviewer_completed_attestation": true, "payouts": ["https://nearblocks.io/txns/1"]}))
+                .unwrap(),
+            None,
+        );
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert!(proposal.snapshot.body.latest_version().milestones[0].status.is_funded());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("frol.near".parse().unwrap())
+            .build());
+        contract.edit_proposal_milestone(0, 0, MilestoneStatus::Delivered);
+    }
+
+    fn setup_milestone_proposal_edit() -> (Contract, ProposalBodyV3) {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("bob.near".parse().unwrap())
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let mut proposal = fake_proposal(0, "Marketing", &[]);
+        let body = proposal.snapshot.body.latest_version();
+        proposal.snapshot.body = body.clone().into();
+        contract.proposals.push(&proposal.into());
+        (contract, body)
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Milestone statuses can only be changed with edit_proposal_milestone"
+    )]
+    pub fn test_edit_proposal_funding_own_milestone() {
+        let (mut contract, mut body) = setup_milestone_proposal_edit();
+        body.milestones[0].status =
+            MilestoneStatus::Funded { payouts: vec!["https://nearblocks.io/txns/1".to_string()] };
+        contract.edit_proposal(0, body.into(), HashSet::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "The proposal has milestones, it can only be edited with a V3 body")]
+    pub fn test_edit_proposal_with_older_body_version() {
+        let (mut contract, body) = setup_milestone_proposal_edit();
+        let mut second_milestone = body.milestones[0].clone();
+        second_milestone.description = "second".to_string();
+        let mut edited = body.clone();
+        edited.milestones.push(second_milestone);
+        contract.edit_proposal(0, edited.clone().into(), HashSet::new());
+
+        let legacy: ProposalBodyV2 = edited.into();
+        assert_eq!(legacy.requested_sponsorship_usd_amount, 2000);
+        contract.edit_proposal(0, legacy.into(), HashSet::new());
+    }
+
+    #[test]
+    pub fn test_proposal_workflow() {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("frol.near".parse().unwrap())
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &["wg-protocol"]).into());
+
+        assert!(contract
+            .get_allowed_proposal_transitions(0, "frol.near".parse().unwrap())
+            .is_empty());
+        let editor_transitions = vec![
+            TimelineStatusKind::Draft,
+            TimelineStatusKind::Review,
+            TimelineStatusKind::Cancelled,
+        ];
+        assert_eq!(
+            contract.get_allowed_proposal_transitions(0, "bob.near".parse().unwrap()),
+            editor_transitions
+        );
+
+        add_team_member(&mut contract, "frol.near", "starts-with:wg-", [ActionType::EditPost]);
+        assert_eq!(
+            contract.get_allowed_proposal_transitions(0, "frol.near".parse().unwrap()),
+            editor_transitions
+        );
+
+        let workflow = workflow_with_role(
+            contract.get_proposal_workflow(),
+            TimelineStatusKind::Draft,
+            TimelineStatusKind::Rejected,
+            ProposalRole::Supervisor,
+        );
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("devhub.near".parse().unwrap())
+            .build());
+        contract.set_proposal_workflow(workflow);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("frol.near".parse().unwrap())
+            .build());
+        assert_eq!(
+            contract.get_allowed_proposal_transitions(0, "frol.near".parse().unwrap()),
+            vec![
+                TimelineStatusKind::Draft,
+                TimelineStatusKind::Review,
+                TimelineStatusKind::Rejected,
+                TimelineStatusKind::Cancelled
+            ]
+        );
+        contract.edit_proposal_versioned_timeline(
+            0,
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V2", "status": "REJECTED", "sponsor_requested_review": false, "reviewer_completed_attestation": false, "kyc_verified": false}))
+                .unwrap(),
+            None,
+        );
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert_eq!(
+            proposal.snapshot.body.latest_version().timeline.latest_version().kind(),
+            TimelineStatusKind::Rejected
+        );
+    }
+
+    fn workflow_with_role(
+        workflow: &ProposalWorkflow,
+        from: TimelineStatusKind,
+        to: TimelineStatusKind,
+        role: ProposalRole,
+    ) -> ProposalWorkflow {
+        let mut workflow = workflow.clone();
+        for transition in workflow.transitions.iter_mut() {
+            if transition.from == from && transition.to == to {
+                transition.roles.insert(role.clone());
+            }
+        }
+        workflow
+    }
+
+    #[test]
+    pub fn test_proposal_workflow_without_edit_permission() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &[]).into());
+        let workflow = workflow_with_role(
+            contract.get_proposal_workflow(),
+            TimelineStatusKind::Draft,
+            TimelineStatusKind::Rejected,
+            ProposalRole::Supervisor,
+        );
+        contract.set_proposal_workflow(workflow);
+
+        // The supervisor is not allowed to edit the proposal, but the role is enough.
+        testing_env!(rfp_deadline_context("frol.near"));
+        assert!(!contract.is_allowed_to_edit_proposal(0, None));
+        contract.edit_proposal_versioned_timeline(
+            0,
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V2", "status": "REJECTED", "sponsor_requested_review": false, "reviewer_completed_attestation": false, "kyc_verified": false}))
+                .unwrap(),
+            None,
+        );
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert_eq!(
+            proposal.snapshot.body.latest_version().timeline.latest_version().kind(),
+            TimelineStatusKind::Rejected
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "This account is not allowed to change proposal status")]
+    pub fn test_proposal_workflow_without_role() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &[]).into());
+
+        testing_env!(rfp_deadline_context("frol.near"));
+        contract.edit_proposal_versioned_timeline(
+            0,
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V2", "status": "REJECTED", "sponsor_requested_review": false, "reviewer_completed_attestation": false, "kyc_verified": false}))
+                .unwrap(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The workflow has more than one transition from Draft to Rejected")]
+    pub fn test_proposal_workflow_with_duplicate_transition() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        let mut workflow = contract.get_proposal_workflow().clone();
+        workflow.transitions.push(ProposalTransition {
+            from: TimelineStatusKind::Draft,
+            to: TimelineStatusKind::Rejected,
+            roles: HashSet::from([ProposalRole::Supervisor]),
+        });
+        contract.set_proposal_workflow(workflow);
+    }
+
+    #[test]
+    #[should_panic(expected = "Team funding does not exist")]
+    pub fn test_proposal_workflow_with_unknown_team() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        let workflow = workflow_with_role(
+            contract.get_proposal_workflow(),
+            TimelineStatusKind::Review,
+            TimelineStatusKind::Approved,
+            ProposalRole::Team("funding".to_string()),
+        );
+        contract.set_proposal_workflow(workflow);
+    }
+
+    #[test]
+    pub fn test_proposal_timeline_history() {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("devhub.near".parse().unwrap())
+            .block_height(42)
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &[]).into());
+        assert!(contract.get_proposal_timeline_history(0).is_empty());
+
+        let review: VersionedTimelineStatus = near_sdk::serde_json::from_value(json!({
+            "timeline_version": "V2",
+            "status": "REVIEW",
+            "sponsor_requested_review": false,
+            "reviewer_completed_attestation": false,
+            "kyc_verified": false
+        }))
+        .unwrap();
+        contract.edit_proposal_versioned_timeline(
+            0,
+            review.clone(),
+            Some("Ready for review".to_string()),
+        );
+        // Editing the timeline without changing the status is not a transition.
+        contract.edit_proposal_versioned_timeline(0, review, None);
+
+        assert_eq!(
+            contract.get_proposal_timeline_history(0),
+            vec![TimelineTransition {
+                from: TimelineStatusKind::Draft,
+                to: TimelineStatusKind::Review,
+                actor: "devhub.near".parse().unwrap(),
+                block_height: 42,
+                reason: Some("Ready for review".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_get_proposal_diff() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let mut proposal = fake_proposal(0, "Marketing", &["test"]);
+        let old_snapshot = fake_proposal(0, "Marketing", &[]).snapshot;
+        proposal.snapshot_history.push(old_snapshot);
+        let mut body = proposal.snapshot.body.clone().latest_version();
+        body.name = "renamed".to_string();
+        body.milestones[0].usd_amount = 2000;
+        body.milestones[0].description = "first milestone".to_string();
+        body.milestones[0].due_date = 200;
+        body.milestones[0].status = MilestoneStatus::Delivered;
+        proposal.snapshot.body = body.into();
+        contract.proposals.push(&proposal.into());
+
+        assert_eq!(
+            contract.get_proposal_diff(0, 0, 1),
+            vec![
+                FieldChange {
+                    field: "name".to_string(),
+                    from: json!("proposal 0"),
+                    to: json!("renamed")
+                },
+                FieldChange { field: "labels".to_string(), from: json!([]), to: json!(["test"]) },
+                FieldChange {
+                    field: "requested_sponsorship_usd_amount".to_string(),
+                    from: json!("1000"),
+                    to: json!("2000")
+                },
+                FieldChange {
+                    field: "milestones".to_string(),
+                    from: json!([{
+                        "description": "sum",
+                        "usd_amount": "1000",
+                        "due_date": "0",
+                        "status": { "status": "PENDING" },
+                    }]),
+                    to: json!([{
+                        "description": "first milestone",
+                        "usd_amount": "2000",
+                        "due_date": "200",
+                        "status": { "status": "DELIVERED" },
+                    }])
+                },
+            ]
+        );
+        assert!(contract.get_proposal_diff(0, 1, 1).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot 2 of proposal 0 not found")]
+    pub fn test_get_proposal_diff_out_of_range() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &[]).into());
+        contract.get_proposal_diff(0, 0, 2);
+    }
+
+    fn fake_rfp_snapshot(block_height: u64, name: &str) -> RFPSnapshot {
+        let body: RFPBodyV0 = near_sdk::serde_json::from_value(json!({
+            "name": name,
+            "summary": "sum",
+            "description": "description",
+            "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
+            "submission_deadline": "0"
+        }))
+        .unwrap();
+        RFPSnapshot {
+            editor_id: "bob.near".parse().unwrap(),
+            timestamp: block_height,
+            block_height,
+            labels: HashSet::new(),
+            body: body.into(),
+            linked_proposals: HashSet::new(),
+        }
+    }
+
+    #[test]
+    pub fn test_get_rfp_at_block_height() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.rfps.push(
+            &RFP {
+                id: 0,
+                author_id: "bob.near".parse().unwrap(),
+                social_db_post_block_height: 0,
+                snapshot: fake_rfp_snapshot(20, "third"),
+                snapshot_history: vec![
+                    fake_rfp_snapshot(5, "first"),
+                    fake_rfp_snapshot(10, "second"),
+                ],
+                legacy_snapshot_history: vec![],
+            }
+            .into(),
+        );
+        let name_at = |block_height: u64| {
+            contract.get_rfp_at_block_height(0, block_height.into()).map(|rfp| {
+                let rfp: RFP = rfp.into();
+                assert!(rfp.snapshot_history.iter().all(|s| s.block_height < block_height));
+                rfp.snapshot.body.latest_version().name
+            })
+        };
+        assert_eq!(name_at(4), None);
+        assert_eq!(name_at(5).as_deref(), Some("first"));
+        assert_eq!(name_at(19).as_deref(), Some("second"));
+        assert_eq!(name_at(100).as_deref(), Some("third"));
+
+        // The view keeps the block heights of the previous snapshots.
+        let view = near_sdk::serde_json::to_value(contract.get_rfp(0)).unwrap();
+        assert_eq!(view["rfp_version"], json!("V0"));
+        assert_eq!(view["snapshot_history"], json!([5, 10]));
+
+        let diff = contract.get_rfp_diff(0, 0, 2);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].field, "name");
+    }
+
+    #[test]
+    #[should_panic(expected = "RFP 0 at block height 7 is not stored")]
+    pub fn test_get_legacy_rfp_at_block_height() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.rfps.push(&VersionedRFP::V0(RFPV0 {
+            id: 0,
+            author_id: "bob.near".parse().unwrap(),
+            social_db_post_block_height: 0,
+            snapshot: fake_rfp_snapshot(10, "second"),
+            snapshot_history: vec![5],
+        }));
+        assert!(contract.get_rfp_at_block_height(0, 10.into()).is_some());
+        assert!(contract.get_rfp_at_block_height(0, 4.into()).is_none());
+        contract.get_rfp_at_block_height(0, 7.into());
+    }
+
+    fn fake_rfp(submission_deadline: u64) -> RFP {
+        let mut snapshot = fake_rfp_snapshot(0, "rfp");
+        let mut body = snapshot.body.latest_version();
+        body.submission_deadline = submission_deadline;
+        snapshot.body = body.into();
+        RFP {
+            id: 0,
+            author_id: "devhub.near".parse().unwrap(),
+            social_db_post_block_height: 0,
+            snapshot,
+            snapshot_history: vec![],
+            legacy_snapshot_history: vec![],
+        }
+    }
+
+    fn rfp_deadline_context(predecessor: &str) -> VMContext {
+        VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .block_timestamp(100)
+            .build()
+    }
+
+    fn join_community_context(predecessor: &str) -> VMContext {
+        VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .attached_deposit(near_sdk::NearToken::from_millinear(10))
+            .block_timestamp(100)
+            .build()
+    }
+
+    #[test]
+    pub fn test_link_proposal_to_rfp_keeps_rfp_history() {
+        testing_env!(rfp_deadline_context("bob.near"));
+        let mut contract = Contract::new();
+        contract.rfps.push(&fake_rfp(200).into());
+        let proposal = fake_proposal(0, "Marketing", &[]);
+        let old_body = proposal.snapshot.body.clone();
+        let mut body = old_body.clone().latest_version();
+        body.linked_rfp = Some(0);
+        contract.proposals.push(&proposal.into());
+
+        contract.update_and_check_rfp_link(0, body.into(), Some(old_body), HashSet::new());
+
+        let rfp = contract.load_rfp(0);
+        assert_eq!(rfp.snapshot.linked_proposals, HashSet::from([0]));
+        assert!(rfp.snapshot_history.is_empty());
+    }
+
+    #[test]
+    pub fn test_close_rfp_submissions() {
+        testing_env!(rfp_deadline_context("alice.near"));
+        let mut contract = Contract::new();
+        contract.rfps.push(&fake_rfp(50).into());
+
+        contract.close_rfp_submissions(0);
+
+        let rfp = contract.load_rfp(0);
+        assert!(matches!(
+            rfp.snapshot.body.latest_version().timeline,
+            RFPTimelineStatus::Evaluation
+        ));
+        assert_eq!(rfp.snapshot_history.len(), 1);
+        assert_eq!(contract.get_change_log().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The submission deadline of the RFP 0 has not passed yet")]
+    pub fn test_close_rfp_submissions_before_deadline() {
+        testing_env!(rfp_deadline_context("alice.near"));
+        let mut contract = Contract::new();
+        contract.rfps.push(&fake_rfp(200).into());
+
+        contract.close_rfp_submissions(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The submission deadline of the RFP 0 has passed")]
+    pub fn test_link_rfp_after_deadline() {
+        testing_env!(rfp_deadline_context("bob.near"));
+        let mut contract = Contract::new();
+        contract.rfps.push(&fake_rfp(50).into());
+        contract.proposals.push(&fake_proposal(0, "Marketing", &[]).into());
+
+        contract.edit_proposal_linked_rfp(0, Some(0));
+    }
+
+    fn setup_rfp_evaluation() -> Contract {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        let mut rfp = fake_rfp(50);
+        let mut body = rfp.snapshot.body.latest_version();
+        body.timeline = RFPTimelineStatus::Evaluation;
+        rfp.snapshot.body = body.into();
+        rfp.snapshot.linked_proposals = HashSet::from([0, 1]);
+        contract.rfps.push(&rfp.into());
+        let approved: VersionedTimelineStatus = near_sdk::serde_json::from_value(json!({
+            "timeline_version": "V2",
+            "status": "APPROVED",
+            "sponsor_requested_review": true,
+            "reviewer_completed_attestation": false,
+            "kyc_verified": false
+        }))
+        .unwrap();
+        for id in 0..2 {
+            let mut proposal = fake_proposal(id, "Marketing", &[]);
+            let mut body = proposal.snapshot.body.latest_version();
+            body.linked_rfp = Some(0);
+            if id == 0 {
+                body.set_timeline(approved.clone());
+            }
+            proposal.snapshot.body = body.into();
+            contract.proposals.push(&proposal.into());
+        }
+        contract.set_rfp_evaluation(
+            0,
+            vec![
+                EvaluationCriterion { name: "quality".to_string(), weight: 2 },
+                EvaluationCriterion { name: "cost".to_string(), weight: 1 },
+            ],
+            Member::Account("alice.near".parse().unwrap()),
+            true,
+        );
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.submit_rfp_scorecard(0, 0, vec![5, 5]);
+        contract.submit_rfp_scorecard(0, 1, vec![10, 0]);
+        contract
+    }
+
+    #[test]
+    pub fn test_rfp_ranking() {
+        let contract = setup_rfp_evaluation();
+        assert_eq!(
+            contract.get_rfp_ranking(0),
+            vec![
+                ProposalRanking { proposal_id: 1, total_weighted_score: 20, scorecards_count: 1 },
+                ProposalRanking { proposal_id: 0, total_weighted_score: 15, scorecards_count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the evaluation committee can score proposals")]
+    pub fn test_rfp_scorecard_outside_committee() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("bob.near"));
+        contract.submit_rfp_scorecard(0, 0, vec![10, 10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "without the top-ranked proposal selected")]
+    pub fn test_rfp_selection_follows_ranking() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.edit_rfp_timeline(0, RFPTimelineStatus::ProposalSelected);
+    }
+
+    #[test]
+    pub fn test_rfp_selection_ranking_override() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.override_rfp_ranking(0, "The top proposal is out of budget".to_string());
+        contract.edit_rfp_timeline(0, RFPTimelineStatus::ProposalSelected);
+
+        let rfp = contract.load_rfp(0);
+        assert!(rfp.snapshot.body.latest_version().timeline.is_proposal_selected());
+    }
+
+    fn select_rfp_proposals(contract: &mut Contract, selected: Vec<(ProposalId, u32)>) {
+        let rfp = contract.load_rfp(0);
+        let mut body = rfp.snapshot.body.latest_version();
+        body.timeline = RFPTimelineStatus::ProposalSelected;
+        body.total_budget_usd_amount = Some(1000);
+        body.selected_proposals = selected
+            .into_iter()
+            .map(|(proposal_id, allocated_usd_amount)| SelectedProposal {
+                proposal_id,
+                allocated_usd_amount,
+            })
+            .collect();
+        contract.edit_rfp(0, body.into(), rfp.snapshot.labels);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Proposal 0 is selected in the RFP 0, remove it from the selected proposals first"
+    )]
+    pub fn test_reject_selected_proposal() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.override_rfp_ranking(0, "The top proposal is out of budget".to_string());
+        select_rfp_proposals(&mut contract, vec![(0, 600)]);
+
+        contract.edit_proposal_versioned_timeline(
+            0,
+            near_sdk::serde_json::from_value(json!({"timeline_version": "V2", "status": "REJECTED", "sponsor_requested_review": true, "reviewer_completed_attestation": false, "kyc_verified": false}))
+                .unwrap(),
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Proposal 0 is selected in the RFP 0, remove it from the selected proposals first"
+    )]
+    pub fn test_unlink_selected_proposal() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.override_rfp_ranking(0, "The top proposal is out of budget".to_string());
+        select_rfp_proposals(&mut contract, vec![(0, 600)]);
+
+        let proposal: Proposal = contract.get_proposal(0).into();
+        let old_body = proposal.snapshot.body;
+        let mut body = old_body.clone().latest_version();
+        body.linked_rfp = None;
+        contract.update_and_check_rfp_link(0, body.into(), Some(old_body), HashSet::new());
+    }
+
+    #[test]
+    pub fn test_rfp_selected_proposals() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.override_rfp_ranking(0, "The top proposal is out of budget".to_string());
+        select_rfp_proposals(&mut contract, vec![(0, 600)]);
+
+        let rfp = contract.load_rfp(0);
+        let body = rfp.snapshot.body.latest_version();
+        assert!(body.timeline.is_proposal_selected());
+        assert_eq!(body.total_allocated_usd_amount(), 600);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The amounts allocated to the selected proposals exceed the RFP budget"
+    )]
+    pub fn test_rfp_selected_proposals_over_budget() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.override_rfp_ranking(0, "The top proposal is out of budget".to_string());
+        select_rfp_proposals(&mut contract, vec![(0, 1001)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The RFP needs a budget before proposals can be selected")]
+    pub fn test_rfp_selected_proposals_without_budget() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let rfp = contract.load_rfp(0);
+        let mut body = rfp.snapshot.body.latest_version();
+        assert_eq!(body.total_budget_usd_amount, None);
+        assert!(body.budget_paid_in_currency.is_none());
+        body.timeline = RFPTimelineStatus::ProposalSelected;
+        body.selected_proposals =
+            vec![SelectedProposal { proposal_id: 0, allocated_usd_amount: 0 }];
+        contract.edit_rfp(0, body.into(), rfp.snapshot.labels);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal 1 can't be selected because it was not approved")]
+    pub fn test_rfp_selected_proposal_not_approved() {
+        let mut contract = setup_rfp_evaluation();
+        testing_env!(rfp_deadline_context("devhub.near"));
+        select_rfp_proposals(&mut contract, vec![(0, 500), (1, 500)]);
+    }
+
+    fn setup_registered_labels() -> Contract {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.set_global_labels(vec![LabelInfoExtended {
+            value: "test".to_string(),
+            title: None,
+            color: None,
+        }]);
+        contract.set_require_registered_proposal_labels(true);
+        contract
+    }
+
+    #[test]
+    pub fn test_remap_unregistered_proposal_labels() {
+        let mut contract = setup_registered_labels();
+        for (id, label) in [(0, "tset"), (1, "test"), (2, "other")] {
+            let mut proposal = fake_proposal(id, "Marketing", &[label]);
+            proposal.snapshot_history.push(proposal.snapshot.clone());
+            contract.proposals.push(&proposal.into());
+            contract.label_to_proposals.insert(&label.to_string(), &HashSet::from([id]));
+        }
+        assert_eq!(contract.get_unregistered_proposal_labels(), vec!["other", "tset"]);
+
+        let remaining = contract.remap_unregistered_proposal_labels(
+            [("tset".to_string(), "test".to_string())].into_iter().collect(),
+        );
+
+        assert_eq!(remaining, vec!["other"]);
+        assert_eq!(contract.get_proposals_by_label("test".to_string()), vec![0, 1]);
+        assert!(contract.get_proposals_by_label("tset".to_string()).is_empty());
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert_eq!(proposal.snapshot.labels, HashSet::from(["test".to_string()]));
+        assert_eq!(proposal.snapshot_history[0].labels, HashSet::from(["tset".to_string()]));
+        assert_eq!(contract.get_change_log().len(), 1);
+    }
+
+    #[test]
+    pub fn test_remap_unregistered_proposal_labels_in_batches() {
+        let mut contract = setup_registered_labels();
+        for id in 0..60 {
+            contract.proposals.push(&fake_proposal(id, "Marketing", &["tset"]).into());
+        }
+        contract.label_to_proposals.insert(&"tset".to_string(), &(0..60).collect());
+        let remap: HashMap<_, _> = [("tset".to_string(), "test".to_string())].into();
+
+        assert_eq!(contract.remap_unregistered_proposal_labels(remap.clone()), vec!["tset"]);
+        assert_eq!(contract.get_proposals_by_label("tset".to_string()).len(), 10);
+        assert!(contract.remap_unregistered_proposal_labels(remap).is_empty());
+        assert_eq!(contract.get_proposals_by_label("test".to_string()).len(), 60);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label tset is not registered")]
+    pub fn test_edit_proposal_with_unregistered_label() {
+        let mut contract = setup_registered_labels();
+        let proposal = fake_proposal(0, "Marketing", &[]);
+        let body = proposal.snapshot.body.clone();
+        contract.proposals.push(&proposal.into());
+
+        contract.edit_proposal(0, body, HashSet::from(["tset".to_string()]));
+    }
+
+    fn setup_labeled_proposal_and_rfp(contract: &mut Contract, label: &str) {
+        let mut proposal = fake_proposal(0, "Marketing", &[label]);
+        proposal.snapshot_history.push(proposal.snapshot.clone());
+        contract.proposals.push(&proposal.into());
+        contract.label_to_proposals.insert(&label.to_string(), &HashSet::from([0]));
+        let mut rfp = fake_rfp(200);
+        rfp.snapshot.labels = HashSet::from([label.to_string()]);
+        contract.rfps.push(&rfp.into());
+        contract.label_to_rfps.insert(&label.to_string(), &HashSet::from([0]));
+    }
+
+    #[test]
+    pub fn test_rename_label() {
+        let mut contract = setup_registered_labels();
+        contract.set_global_labels(vec![LabelInfoExtended {
+            value: "test".to_string(),
+            title: Some("Test".to_string()),
+            color: None,
+        }]);
+        setup_labeled_proposal_and_rfp(&mut contract, "test");
+
+        assert_eq!(contract.rename_label("test".to_string(), "renamed".to_string()), 0);
+
+        let labels = contract.get_global_labels();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].value, "renamed");
+        assert_eq!(labels[0].title, Some("Test".to_string()));
+        assert_eq!(contract.get_proposals_by_label("renamed".to_string()), vec![0]);
+        assert_eq!(contract.get_rfps_by_label("renamed".to_string()), vec![0]);
+        assert!(contract.get_proposals_by_label("test".to_string()).is_empty());
+        assert!(contract.get_rfps_by_label("test".to_string()).is_empty());
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert_eq!(proposal.snapshot.labels, HashSet::from(["renamed".to_string()]));
+        assert_eq!(proposal.snapshot_history[0].labels, HashSet::from(["test".to_string()]));
+        let rfp = contract.load_rfp(0);
+        assert_eq!(rfp.snapshot.labels, HashSet::from(["renamed".to_string()]));
+        assert_eq!(contract.get_change_log().len(), 2);
+    }
+
+    #[test]
+    pub fn test_rename_restricted_label() {
+        let mut contract = setup_registered_labels();
+        setup_labeled_proposal_and_rfp(&mut contract, "test");
+        contract.set_restricted_rules(RulesList {
+            rules: HashMap::from([(
+                Rule::ExactMatch("test".to_string()),
+                RuleMetadata { description: "restricted".to_string() }.into(),
+            )]),
+        });
+        add_team_member(&mut contract, "alice.near", "test", [ActionType::UseLabels]);
+
+        testing_env!(rfp_deadline_context("devhub.near"));
+        assert_eq!(contract.rename_label("test".to_string(), "renamed".to_string()), 0);
+
+        assert!(!contract.is_restricted_label("test".to_string()));
+        assert!(contract.is_restricted_label("renamed".to_string()));
+        assert!(contract.is_allowed_to_use_labels(
+            Some("alice.near".parse().unwrap()),
+            vec!["renamed".to_string()]
+        ));
+        assert!(!contract.is_allowed_to_use_labels(
+            Some("bob.near".parse().unwrap()),
+            vec!["renamed".to_string()]
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Label test is restricted by a rule that does not apply to renamed")]
+    pub fn test_rename_label_restricted_by_prefix() {
+        let mut contract = setup_registered_labels();
+        contract.set_restricted_rules(RulesList {
+            rules: HashMap::from([(
+                Rule::StartsWith("te".to_string()),
+                RuleMetadata { description: "restricted".to_string() }.into(),
+            )]),
+        });
+        contract.rename_label("test".to_string(), "renamed".to_string());
+    }
+
+    fn setup_global_labels(contract: &mut Contract, labels: &[&str]) {
+        contract.set_global_labels(
+            labels
+                .iter()
+                .map(|value| LabelInfoExtended {
+                    value: value.to_string(),
+                    title: None,
+                    color: None,
+                })
+                .collect(),
+        );
+    }
+
+    #[test]
+    pub fn test_merge_and_deprecate_labels() {
+        let mut contract = setup_registered_labels();
+        setup_global_labels(&mut contract, &["test", "other"]);
+        setup_labeled_proposal_and_rfp(&mut contract, "test");
+
+        assert_eq!(contract.merge_labels("test".to_string(), "other".to_string()), 0);
+
+        let labels: Vec<_> =
+            contract.get_global_labels().into_iter().map(|label| label.value).collect();
+        assert_eq!(labels, vec!["other"]);
+        assert_eq!(contract.get_proposals_by_label("other".to_string()), vec![0]);
+        assert_eq!(contract.get_rfps_by_label("other".to_string()), vec![0]);
+
+        contract.deprecate_label("other".to_string());
+
+        assert_eq!(contract.get_deprecated_labels(), vec!["other"]);
+        assert_eq!(contract.get_global_labels().len(), 1);
+        assert_eq!(contract.get_proposals_by_label("other".to_string()), vec![0]);
+        let proposal: Proposal = contract.get_proposal(0).into();
+        assert_eq!(proposal.snapshot.labels, HashSet::from(["other".to_string()]));
+
+        // Entries that already have the label keep it when they are edited.
+        let rfp = contract.load_rfp(0);
+        contract.edit_rfp(0, rfp.snapshot.body, rfp.snapshot.labels);
+        assert_eq!(contract.get_rfps_by_label("other".to_string()), vec![0]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Label starts-with:test can't be used, it reads as an access control rule"
+    )]
+    pub fn test_set_global_label_that_mimics_rule() {
+        let mut contract = setup_registered_labels();
+        setup_global_labels(&mut contract, &["test", "starts-with:test"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label not:test can't be used, it reads as an access control rule")]
+    pub fn test_add_proposal_with_label_that_mimics_rule() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let body: ProposalBodyV0 = near_sdk::serde_json::from_value(json!({
+            "proposal_body_version": "V0",
+            "name": "another post",
+            "description": "Some description",
+            "category": "Marketing",
+            "summary": "sum",
+            "linked_proposals": [],
+            "requested_sponsorship_usd_amount": "1000000000",
+            "requested_sponsorship_paid_in_currency": "USDT",
+            "receiver_account": "polyprogrammist.near",
+            "supervisor": "frol.near",
+            "requested_sponsor": "neardevdao.near",
+            "payouts": [],
+            "timeline": {"status": "DRAFT"}
+        }))
+        .unwrap();
+        contract.add_proposal(
+            VersionedProposalBody::V0(body),
+            HashSet::from(["not:test".to_string()]),
+            Some(0),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Label other is deprecated")]
+    pub fn test_add_deprecated_label() {
+        let mut contract = setup_registered_labels();
+        setup_global_labels(&mut contract, &["test", "other"]);
+        setup_labeled_proposal_and_rfp(&mut contract, "test");
+        contract.deprecate_label("other".to_string());
+
+        let rfp = contract.load_rfp(0);
+        contract.edit_rfp(
+            0,
+            rfp.snapshot.body,
+            HashSet::from(["test".to_string(), "other".to_string()]),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Label test is restricted by a rule that does not apply to other")]
+    pub fn test_merge_label_restricted_by_prefix() {
+        let mut contract = setup_registered_labels();
+        setup_global_labels(&mut contract, &["test", "other"]);
+        contract.set_restricted_rules(RulesList {
+            rules: HashMap::from([(
+                Rule::StartsWith("te".to_string()),
+                RuleMetadata { description: "restricted".to_string() }.into(),
+            )]),
+        });
+        contract.merge_labels("test".to_string(), "other".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Label other is already registered")]
+    pub fn test_rename_label_to_registered_label() {
+        let mut contract = setup_registered_labels();
+        contract.set_global_labels(
+            ["test", "other"]
+                .into_iter()
+                .map(|value| LabelInfoExtended {
+                    value: value.to_string(),
+                    title: None,
+                    color: None,
+                })
+                .collect(),
+        );
+
+        contract.rename_label("test".to_string(), "other".to_string());
+    }
+
+    fn add_team_member(
+        contract: &mut Contract,
+        account: &str,
+        rule: &str,
+        actions: impl IntoIterator<Item = ActionType>,
+    ) {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let team = Member::Team("delegates".to_string());
+        contract.add_member(
+            team.clone(),
+            MemberMetadata {
+                permissions: HashMap::from([(
+                    Rule::from(rule.to_string()),
+                    actions.into_iter().collect(),
+                )]),
+                ..Default::default()
+            }
+            .into(),
+        );
+        contract.add_member(
+            Member::Account(account.parse().unwrap()),
+            MemberMetadata { parents: HashSet::from([team]), ..Default::default() }.into(),
+        );
+        testing_env!(rfp_deadline_context(account));
+    }
+
+    #[test]
+    pub fn test_delegated_label_and_category_management() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        add_team_member(
+            &mut contract,
+            "alice.near",
+            "*",
+            [ActionType::ManageLabels, ActionType::ManageCategories],
+        );
+
+        contract.set_global_labels(vec![LabelInfoExtended {
+            value: "test".to_string(),
+            title: None,
+            color: None,
+        }]);
+        contract.set_allowed_categories(vec!["Marketing".to_string()]);
+
+        assert_eq!(contract.get_global_labels()[0].value, "test");
+        assert_eq!(contract.get_allowed_categories(), vec!["Marketing"]);
+        assert!(!contract.is_allowed_to_write_rfps("alice.near".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the admin and moderators can create new add-ons")]
+    pub fn test_delegated_permission_not_granted() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        add_team_member(&mut contract, "alice.near", "*", [ActionType::WriteRfp]);
+
+        assert!(contract.is_allowed_to_write_rfps("alice.near".parse().unwrap()));
+        contract.create_addon(fake_addon("test".to_string()));
+    }
+
+    #[test]
+    pub fn test_delegated_proposal_timeline_change() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.proposals.push(&fake_proposal(0, "Marketing", &["wg-protocol"]).into());
+        contract.proposals.push(&fake_proposal(1, "Marketing", &["funding"]).into());
+        add_team_member(
+            &mut contract,
+            "alice.near",
+            "starts-with:wg-",
+            [ActionType::ChangeProposalTimeline],
+        );
+
+        assert!(contract
+            .get_allowed_proposal_transitions(0, "alice.near".parse().unwrap())
+            .contains(&TimelineStatusKind::Review));
+        assert!(contract
+            .get_allowed_proposal_transitions(1, "alice.near".parse().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_explain_permissions() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.set_restricted_rules(RulesList {
+            rules: HashMap::from([(
+                Rule::StartsWith("wg-".to_string()),
+                RuleMetadata { description: "For working groups only".to_string() }.into(),
+            )]),
+        });
+        contract.proposals.push(&fake_proposal(0, "Marketing", &["wg-protocol"]).into());
+        add_team_member(&mut contract, "alice.near", "wg-tools", [ActionType::EditPost]);
+
+        let explanation = contract.explain_proposal_permissions("alice.near".parse().unwrap(), 0);
+
+        assert!(!explanation.is_author);
+        assert!(!explanation.can_edit_proposal);
+        let explanation = explanation.labels;
+        assert_eq!(explanation.restricted_rules, vec![Rule::StartsWith("wg-".to_string())]);
+        assert!(!explanation.is_owner);
+        assert_eq!(explanation.grants.len(), 1);
+        assert_eq!(
+            explanation.grants[0].path,
+            vec![
+                Member::Account("alice.near".parse().unwrap()),
+                Member::Team("delegates".to_string())
+            ]
+        );
+        assert!(!explanation.grants[0].granted);
+        assert!(!explanation.can_use_labels);
+        assert_eq!(
+            contract.explain_permissions(
+                "alice.near".parse().unwrap(),
+                vec!["wg-protocol".to_string()]
+            ),
+            explanation
+        );
+
+        let explanation = contract.explain_proposal_permissions("bob.near".parse().unwrap(), 0);
+        assert!(explanation.is_author);
+        assert!(explanation.can_edit_proposal);
+    }
+
+    #[test]
+    pub fn test_access_control_audit_log() {
+        let context = VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("devhub.near".parse().unwrap())
+            .block_height(42)
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        let rules = RulesList {
+            rules: HashMap::from([(
+                Rule::StartsWith("wg-".to_string()),
+                RuleMetadata { description: "For working groups only".to_string() }.into(),
+            )]),
+        };
+        let member = Member::Account("alice.near".parse().unwrap());
+        let metadata: VersionedMemberMetadata =
+            MemberMetadata { description: "Alice".to_string(), ..Default::default() }.into();
+        let edited_metadata: VersionedMemberMetadata =
+            MemberMetadata { description: "Bob".to_string(), ..Default::default() }.into();
+
+        contract.set_restricted_rules(rules.clone());
+        contract.add_member(member.clone(), metadata.clone());
+        contract.edit_member(member.clone(), edited_metadata.clone());
+        contract.remove_member(&member);
+        contract.unset_restricted_rules(vec![Rule::StartsWith("wg-".to_string())]);
+
+        let log = contract.get_access_control_audit_log(None, None);
+        assert!(log.iter().all(|entry| entry.actor.as_str() == "devhub.near"));
+        assert!(log.iter().all(|entry| entry.block_height == 42));
+        let changes: Vec<_> = log.into_iter().map(|entry| entry.change).collect();
+        assert_eq!(
+            changes,
+            vec![
+                AccessControlChange::SetRestrictedRules {
+                    before: RulesList::default(),
+                    after: rules.clone()
+                },
+                AccessControlChange::AddMember { member: member.clone(), after: metadata.clone() },
+                AccessControlChange::EditMember {
+                    member: member.clone(),
+                    before: metadata,
+                    after: edited_metadata.clone()
+                },
+                AccessControlChange::RemoveMember { member, before: edited_metadata },
+                AccessControlChange::UnsetRestrictedRules { before: rules },
+            ]
+        );
+        assert_eq!(contract.get_access_control_audit_log(Some(4), Some(10)).len(), 1);
+    }
+
+    fn setup_moderators(accounts: &[&str]) -> Contract {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        let moderators = Member::Team("moderators".to_string());
+        contract.add_member(moderators.clone(), MemberMetadata::default().into());
+        for account in accounts {
+            contract.add_member(
+                Member::Account(account.parse().unwrap()),
+                MemberMetadata {
+                    parents: HashSet::from([moderators.clone()]),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        contract
+    }
+
+    fn setup_sensitive_action_approvals() -> Contract {
+        let mut contract = setup_moderators(&["alice.near", "bob.near", "carol.near"]);
+        contract.set_sensitive_action_policy(ApprovalPolicy {
+            thresholds: HashMap::from([(SensitiveActionKind::RemoveMember, 2)]),
+            window: 100,
+        });
+        contract
+    }
+
+    #[test]
+    pub fn test_sensitive_action_approvals() {
+        let mut contract = setup_sensitive_action_approvals();
+        let carol = Member::Account("carol.near".parse().unwrap());
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        let id = contract
+            .propose_sensitive_action(SensitiveAction::RemoveMember { member: carol.clone() })
+            .unwrap();
+        let pending = contract.get_pending_sensitive_actions();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].expires_at, 200);
+        assert!(contract.has_moderator("carol.near".parse().unwrap()));
+
+        testing_env!(rfp_deadline_context("bob.near"));
+        assert!(contract.approve_sensitive_action(id));
+
+        assert!(!contract.has_moderator("carol.near".parse().unwrap()));
+        assert!(contract.get_pending_sensitive_actions().is_empty());
+
+        // Actions without a threshold run right away.
+        assert_eq!(
+            contract.propose_sensitive_action(SensitiveAction::SetFeaturedCommunities {
+                handles: vec![]
+            }),
+            None
+        );
+    }
+
+    #[test]
+    pub fn test_sensitive_action_approved_by_delegate() {
+        let mut contract = setup_sensitive_action_approvals();
+        contract.set_sensitive_action_policy(ApprovalPolicy {
+            thresholds: HashMap::from([(SensitiveActionKind::SetFeaturedCommunities, 2)]),
+            window: 100,
+        });
+        add_team_member(&mut contract, "dave.near", "*", [ActionType::ModerateCommunities]);
+        let id = contract
+            .propose_sensitive_action(SensitiveAction::SetFeaturedCommunities { handles: vec![] })
+            .unwrap();
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        assert!(contract.approve_sensitive_action(id));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is not allowed to propose or approve this action")]
+    pub fn test_sensitive_action_proposed_without_permission() {
+        let mut contract = setup_sensitive_action_approvals();
+        add_team_member(&mut contract, "dave.near", "*", [ActionType::ModerateCommunities]);
+        contract.propose_sensitive_action(SensitiveAction::RemoveMember {
+            member: Member::Account("carol.near".parse().unwrap()),
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "This action needs the approval of 2 accounts allowed to call it")]
+    pub fn test_sensitive_action_called_directly() {
+        let mut contract = setup_sensitive_action_approvals();
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.remove_member(&Member::Account("carol.near".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Pending action 0 has expired")]
+    pub fn test_sensitive_action_approval_after_window() {
+        let mut contract = setup_sensitive_action_approvals();
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.propose_sensitive_action(SensitiveAction::RemoveMember {
+            member: Member::Account("carol.near".parse().unwrap()),
+        });
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("bob.near".parse().unwrap())
+            .block_timestamp(201)
+            .build());
+        contract.approve_sensitive_action(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The moderators team moderators can't be left without moderators")]
+    pub fn test_moderators_lock_out() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.edit_member(
+            Member::Account("alice.near".parse().unwrap()),
+            MemberMetadata::default().into(),
+        );
+    }
+
+    #[test]
+    pub fn test_admin_can_remove_last_moderator() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        contract.remove_member(&Member::Account("alice.near".parse().unwrap()));
+        assert!(!contract.has_moderator("alice.near".parse().unwrap()));
+    }
+
+    #[test]
+    pub fn test_set_moderators_team() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        let stewards = Member::Team("stewards".to_string());
+        contract.add_member(stewards.clone(), MemberMetadata::default().into());
+        contract.add_member(
+            Member::Account("bob.near".parse().unwrap()),
+            MemberMetadata { parents: HashSet::from([stewards.clone()]), ..Default::default() }
+                .into(),
+        );
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.set_moderators_team("stewards".to_string());
+
+        assert_eq!(contract.get_moderators_team(), stewards);
+        assert!(contract.has_moderator("bob.near".parse().unwrap()));
+        assert!(!contract.has_moderator("alice.near".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Team devhub/reviewers belongs to a community and can't be the moderators team"
+    )]
+    pub fn test_set_community_team_as_moderators_team() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/reviewed", &["carol.near"]),
+        );
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.set_moderators_team("devhub/reviewers".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The moderators team can't be managed as a community team")]
+    pub fn test_remove_community_team_that_is_moderators_team() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/reviewed", &["carol.near"]),
+        );
+        // Set before community teams were refused as the moderators team.
+        contract.moderators_team = "devhub/reviewers".to_string();
+        contract.remove_community_team("devhub".to_string(), "reviewers".to_string());
+    }
+
+    #[test]
+    pub fn test_two_step_ownership() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        assert_eq!(contract.get_owner().as_str(), "devhub.near");
+
+        contract.propose_owner(Some("alice.near".parse().unwrap()));
+        assert_eq!(contract.get_proposed_owner(), Some("alice.near".parse().unwrap()));
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.accept_owner();
+        assert_eq!(contract.get_owner().as_str(), "alice.near");
+        assert_eq!(contract.get_proposed_owner(), None);
+
+        contract.set_allowed_categories(vec!["Marketing".to_string()]);
+        assert!(contract.is_allowed_to_write_rfps("alice.near".parse().unwrap()));
+        assert_eq!(contract.get_allowed_categories(), vec!["Marketing"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept the ownership")]
+    pub fn test_accept_owner_not_proposed() {
+        testing_env!(rfp_deadline_context("devhub.near"));
+        let mut contract = Contract::new();
+        contract.propose_owner(Some("alice.near".parse().unwrap()));
+
+        testing_env!(rfp_deadline_context("bob.near"));
+        contract.accept_owner();
+    }
+
+    fn insert_fake_community(contract: &mut Contract, handle: &str, admins: &[&str]) {
+        let community = Community {
+            admins: admins.iter().map(|admin| admin.parse().unwrap()).collect(),
+            handle: handle.to_string(),
+            name: "Fake community".to_string(),
+            tag: "fake".to_string(),
+            description: "Fake community".to_string(),
+            logo_url: "https://example.com/logo.png".to_string(),
+            banner_url: "https://example.com/banner.png".to_string(),
+            bio_markdown: None,
+            github_handle: None,
+            telegram_handle: None,
+            twitter_handle: None,
+            website_url: None,
+            addons: vec![],
+        };
+        contract.communities.insert(&community.handle, &community);
+        contract.add_community_to_indexes(&community);
+    }
+
+    fn community_team_metadata(rule: &str, children: &[&str]) -> VersionedMemberMetadata {
+        MemberMetadata {
+            permissions: HashMap::from([(
+                Rule::from(rule.to_string()),
+                HashSet::from([ActionType::UseLabels]),
+            )]),
+            children: children.iter().map(|child| Member::from(child.to_string())).collect(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    pub fn test_community_teams() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        contract.set_restricted_rules(RulesList {
+            rules: HashMap::from([(
+                Rule::StartsWith("devhub/".to_string()),
+                RuleMetadata { description: "DevHub community labels".to_string() }.into(),
+            )]),
+        });
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("starts-with:devhub/", &["dave.near"]),
+        );
+        let reviewed = vec!["devhub/reviewed".to_string()];
+        assert!(contract.is_allowed_to_use_labels(Some("dave.near".parse().unwrap()), reviewed));
+        assert!(!contract.has_moderator("dave.near".parse().unwrap()));
+        assert_eq!(
+            contract.get_community_teams("devhub".to_string()).into_keys().collect::<Vec<_>>(),
+            vec![Member::Team("devhub/reviewers".to_string())]
+        );
+
+        contract.edit_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/approved", &["dave.near"]),
+        );
+        assert!(!contract.is_allowed_to_use_labels(
+            Some("dave.near".parse().unwrap()),
+            vec!["devhub/reviewed".to_string()]
+        ));
+
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.archive_community("devhub".to_string());
+        contract.delete_community("devhub".to_string(), "devhub".to_string());
+        assert!(contract.get_community_teams("devhub".to_string()).is_empty());
+        assert!(contract.validate_access_control().is_empty());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Community team rules must only apply to labels starting with `devhub/`"
+    )]
+    pub fn test_community_team_with_hub_wide_rule() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("*", &[]),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Community teams can only have teams of the same community as parents"
+    )]
+    pub fn test_community_team_inheriting_from_hub_team() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            MemberMetadata {
+                parents: HashSet::from([Member::Team("moderators".to_string())]),
+                ..Default::default()
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only community admins and hub moderators can manage community teams"
+    )]
+    pub fn test_community_team_by_non_admin() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("dave.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/reviewed", &[]),
+        );
+    }
+
+    #[test]
+    pub fn test_community_membership() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        insert_fake_community(&mut contract, "zero", &["carol.near"]);
+        for account in ["dave.near", "erin.near", "frank.near"] {
+            testing_env!(join_community_context(account));
+            contract.join_community("devhub".to_string());
+        }
+        contract.join_community("zero".to_string());
+        assert_eq!(
+            contract.get_account_communities("frank.near".parse().unwrap()),
+            ["devhub", "zero"]
+        );
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.set_community_member_role(
+            "devhub".to_string(),
+            "erin.near".parse().unwrap(),
+            CommunityRole::Contributor,
+        );
+        let page = contract.get_community_members("devhub".to_string(), Some(1), Some(1));
+        assert_eq!(
+            page,
+            vec![CommunityMember {
+                account_id: "erin.near".parse().unwrap(),
+                role: CommunityRole::Contributor,
+                joined_at: 100,
+            }]
+        );
+
+        testing_env!(rfp_deadline_context("dave.near"));
+        contract.leave_community("devhub".to_string());
+        assert_eq!(contract.get_community_members_count("devhub".to_string()), 2);
+        assert!(contract.get_account_communities("dave.near".parse().unwrap()).is_empty());
+
+        testing_env!(rfp_deadline_context("devhub.near"));
+        contract.archive_community("devhub".to_string());
+        assert_eq!(contract.remove_archived_community_members("devhub".to_string()), 0);
+        assert_eq!(contract.get_community_members_count("devhub".to_string()), 0);
+        assert_eq!(contract.get_account_communities("frank.near".parse().unwrap()), ["zero"]);
+        contract.delete_community("devhub".to_string(), "devhub".to_string());
+        assert_eq!(contract.get_account_communities("frank.near".parse().unwrap()), ["zero"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "yoctoNEAR to pay for the storage of the membership")]
+    pub fn test_join_community_without_deposit() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("dave.near"));
+        contract.join_community("devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Remove the members of the community first with remove_archived_community_members"
+    )]
+    pub fn test_delete_community_with_members() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(join_community_context("dave.near"));
+        contract.join_community("devhub".to_string());
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.archive_community("devhub".to_string());
+        contract.delete_community("devhub".to_string(), "devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only community admins and hub moderators can assign community roles"
+    )]
+    pub fn test_community_role_by_non_admin() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(join_community_context("dave.near"));
+        contract.join_community("devhub".to_string());
+        contract.set_community_member_role(
+            "devhub".to_string(),
+            "dave.near".parse().unwrap(),
+            CommunityRole::Moderator,
+        );
+    }
+
+    #[test]
+    pub fn test_community_admin_invitations() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.invite_community_admin("devhub".to_string(), "dave.near".parse().unwrap());
+        let invitations = contract.get_community_admin_invitations("devhub".to_string());
+        assert_eq!(invitations.len(), 1);
+        assert_eq!(invitations[0].invited_by.as_str(), "carol.near");
+
+        testing_env!(rfp_deadline_context("dave.near"));
+        contract.accept_community_admin("devhub".to_string());
+        assert!(contract.get_community_admin_invitations("devhub".to_string()).is_empty());
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.transfer_community_ownership("devhub".to_string(), "dave.near".parse().unwrap());
+        assert_eq!(
+            contract.get_community_owner("devhub".to_string()),
+            Some("dave.near".parse().unwrap())
+        );
+        contract.remove_community_admin("devhub".to_string(), "carol.near".parse().unwrap());
+        assert_eq!(
+            contract.get_community("devhub".to_string()).unwrap().admins,
+            vec!["dave.near".parse::<near_sdk::AccountId>().unwrap()]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Community admins can only be changed with invite_community_admin")]
+    pub fn test_update_community_admins() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near", "dave.near"]);
+
+        testing_env!(rfp_deadline_context("dave.near"));
+        let mut community = contract.get_community("devhub".to_string()).unwrap();
+        community.admins.reverse();
+        contract.update_community("devhub".to_string(), community);
+    }
+
+    #[test]
+    #[should_panic(expected = "The invitation has expired")]
+    pub fn test_expired_community_admin_invitation() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.invite_community_admin("devhub".to_string(), "dave.near".parse().unwrap());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("dave.near".parse().unwrap())
+            .block_timestamp(100 + 7 * 24 * 60 * 60 * 1_000_000_000 + 1)
+            .build());
+        contract.accept_community_admin("devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "A community must have at least one admin")]
+    pub fn test_remove_last_community_admin() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.remove_community_admin("devhub".to_string(), "carol.near".parse().unwrap());
+    }
+
+    #[test]
+    pub fn test_archive_and_restore_community() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        insert_fake_community(&mut contract, "zero", &["carol.near"]);
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.set_featured_communities(vec!["devhub".to_string(), "zero".to_string()]);
+        contract.archive_community("devhub".to_string());
+        assert!(contract.is_community_archived("devhub".to_string()));
+        assert_eq!(contract.get_archived_communities()[0].1.archived_by.as_str(), "alice.near");
+        let metadata = contract.get_all_communities_metadata();
+        assert_eq!(metadata.iter().map(|c| c.handle.as_str()).collect::<Vec<_>>(), ["zero"]);
+        let featured = contract.get_featured_communities();
+        assert_eq!(featured.iter().map(|c| c.handle.as_str()).collect::<Vec<_>>(), ["zero"]);
+        assert!(contract.get_community("devhub".to_string()).is_some());
+
+        contract.restore_community("devhub".to_string());
+        assert!(!contract.is_community_archived("devhub".to_string()));
+        assert_eq!(contract.get_featured_communities().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Community `devhub` is archived and read-only")]
+    pub fn test_archived_community_is_read_only() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.archive_community("devhub".to_string());
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.set_community_addons("devhub".to_string(), vec![]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only archived communities can be deleted, archive the community first"
+    )]
+    pub fn test_delete_community_not_archived() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.delete_community("devhub".to_string(), "devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The confirmation does not match the community handle")]
+    pub fn test_delete_community_without_confirmation() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.archive_community("devhub".to_string());
+        contract.delete_community("devhub".to_string(), "dev".to_string());
+    }
+
+    #[test]
+    pub fn test_rename_community() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.set_featured_communities(vec!["devhub".to_string()]);
+
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.add_community_team(
+            "devhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/reviewed", &["dave.near"]),
+        );
+        testing_env!(join_community_context("dave.near"));
+        contract.join_community("devhub".to_string());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("carol.near".parse().unwrap())
+            .attached_deposit(near_sdk::NearToken::from_near(4))
+            .block_timestamp(100)
+            .build());
+        contract.rename_community("devhub".to_string(), "nearhub".to_string());
+
+        assert_eq!(contract.get_community("devhub".to_string()).unwrap().handle, "nearhub");
+        assert_eq!(contract.get_community_alias("devhub".to_string()).unwrap().handle, "nearhub");
+        assert_eq!(contract.get_featured_communities()[0].handle, "nearhub");
+        assert_eq!(contract.get_account_communities("dave.near".parse().unwrap()), ["nearhub"]);
+        assert_eq!(contract.get_community_members_count("nearhub".to_string()), 1);
+        assert!(contract.community_search_index.get(&"devhub".to_string()).is_none());
+        assert_eq!(
+            contract.community_search_index.get(&"nearhub".to_string()),
+            Some(HashSet::from(["nearhub".to_string()]))
+        );
+        assert_eq!(
+            contract.get_community_teams("nearhub".to_string()).into_keys().collect::<Vec<_>>(),
+            vec![Member::Team("nearhub/reviewers".to_string())]
+        );
+        assert!(contract.validate_access_control().is_empty());
+        // Rules under the old handle stay valid.
+        contract.edit_community_team(
+            "nearhub".to_string(),
+            "reviewers".to_string(),
+            community_team_metadata("devhub/reviewed", &["dave.near"]),
+        );
+
+        // Taking the old handle back reuses its account, which was not released.
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.rename_community("nearhub".to_string(), "devhub".to_string());
+        assert!(contract.get_community_alias("devhub".to_string()).is_none());
+        assert_eq!(contract.get_community("nearhub".to_string()).unwrap().handle, "devhub");
+    }
+
+    #[test]
+    #[should_panic(expected = "The grace period of the community account is not over yet")]
+    pub fn test_release_community_account_during_grace_period() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("carol.near".parse().unwrap())
+            .attached_deposit(near_sdk::NearToken::from_near(4))
+            .block_timestamp(100)
+            .build());
+        contract.rename_community("devhub".to_string(), "nearhub".to_string());
+        contract.release_community_account("devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "`devhub` is not an old community handle")]
+    pub fn test_release_community_account_after_delete() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("carol.near".parse().unwrap())
+            .attached_deposit(near_sdk::NearToken::from_near(4))
+            .block_timestamp(100)
+            .build());
+        contract.rename_community("devhub".to_string(), "nearhub".to_string());
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.archive_community("nearhub".to_string());
+        contract.delete_community("nearhub".to_string(), "nearhub".to_string());
+        assert!(contract.get_community("devhub".to_string()).is_none());
+        insert_fake_community(&mut contract, "devhub", &["dave.near"]);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("devhub.near".parse().unwrap())
+            .predecessor_account_id("dave.near".parse().unwrap())
+            .block_timestamp(u64::MAX)
+            .build());
+        contract.release_community_account("devhub".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Community already exists")]
+    pub fn test_rename_community_to_existing_handle() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        insert_fake_community(&mut contract, "devhub", &["carol.near"]);
+        insert_fake_community(&mut contract, "nearhub", &["carol.near"]);
+        testing_env!(rfp_deadline_context("carol.near"));
+        contract.rename_community("devhub".to_string(), "nearhub".to_string());
+    }
+
+    #[test]
+    pub fn test_get_communities() {
+        let mut contract = setup_moderators(&["alice.near"]);
+        for (handle, name, tag) in [
+            ("devhub", "DevHub", "tooling"),
+            ("devrel", "Developer Relations", "community"),
+            ("zk-proofs", "Devs of ZK", "tooling"),
+            ("music", "WebAssembly Music", "art"),
+        ] {
+            insert_fake_community(&mut contract, handle, &["carol.near"]);
+            testing_env!(rfp_deadline_context("carol.near"));
+            let community = contract.get_community(handle.to_string()).unwrap();
+            contract.update_community(
+                handle.to_string(),
+                Community { name: name.to_string(), tag: tag.to_string(), ..community },
+            );
+        }
+        let handles = |communities: Vec<CommunityMetadata>| {
+            communities.into_iter().map(|community| community.handle).collect::<Vec<_>>()
+        };
+
+        assert_eq!(contract.get_communities(None, None, None).len(), 4);
+        assert_eq!(
+            handles(contract.get_communities(Some(1), Some(2), None)),
+            ["devrel", "zk-proofs"]
+        );
+        assert_eq!(
+            handles(contract.get_communities(None, None, Some("tooling".to_string()))),
+            ["devhub", "zk-proofs"]
+        );
+        // "developer relations" comes before "devhub" and "devs of zk".
+        assert_eq!(
+            handles(contract.get_communities(None, None, Some("dev".to_string()))),
+            ["devrel", "devhub", "zk-proofs"]
+        );
+        assert_eq!(
+            handles(contract.get_communities(Some(1), Some(1), Some("dev".to_string()))),
+            ["devhub"]
+        );
+        assert!(contract.get_communities_by_tag("fake".to_string()).is_empty());
+        assert_eq!(
+            handles(contract.get_communities(None, None, Some("Web".to_string()))),
+            ["music"]
+        );
+
+        testing_env!(rfp_deadline_context("alice.near"));
+        contract.archive_community("devhub".to_string());
+        assert_eq!(handles(contract.get_communities_by_tag("tooling".to_string())), ["zk-proofs"]);
+        contract.delete_community("devhub".to_string(), "devhub".to_string());
+        assert_eq!(
+            contract.tag_to_communities.get(&"tooling".to_string()),
+            Some(HashSet::from(["zk-proofs".to_string()]))
+        );
+        assert!(contract.community_search_index.get(&"devhub".to_string()).is_none());
+        assert_eq!(
+            handles(contract.get_communities(None, None, Some("dev".to_string()))),
+            ["devrel", "zk-proofs"]
+        );
+    }
+
     #[test]
     pub fn test_create_addon() {
         let context = get_context_with_current(false, "bob.near".to_string());
@@ -2,7 +2,13 @@
 //! Should be invocable only by the owner and in most cases should be called only once though the
 //! latter is not asserted.
 
+use crate::access_control::approvals::SensitiveActions;
+use crate::access_control::audit::AccessControlAuditEntry;
 use crate::changelog::ChangeLog;
+use crate::community::search::community_search_terms;
+use crate::proposal::timeline::TimelineTransition;
+use crate::proposal::workflow::ProposalWorkflow;
+use crate::rfp::evaluation::RFPEvaluation;
 use crate::*;
 use near_sdk::store::Lazy;
 use near_sdk::{borsh::to_vec, env, near, NearToken, Promise};
@@ -756,6 +762,1326 @@ pub struct ContractV12 {
     pub change_log: VecDeque<ChangeLog>,
 }
 
+// From ContractV12 to ContractV13
+impl Contract {
+    fn unsafe_add_proposal_workflow() {
+        let ContractV12 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV13 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow: Lazy::new(StorageKey::ProposalWorkflow, ProposalWorkflow::default()),
+        });
+    }
+}
+
+// From ContractV13 to ContractV14
+impl Contract {
+    fn unsafe_add_proposal_timeline_history() {
+        let ContractV13 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV14 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history: LookupMap::new(StorageKey::ProposalTimelineHistory),
+        });
+    }
+}
+
+/// How many RFPs a single `unsafe_migrate` call rewrites, so that it fits into the gas limit.
+const MIGRATED_RFPS_PER_CALL: u64 = 100;
+
+// The state layout stays the same as in ContractV14, only the RFPs are rewritten to the version
+// that keeps the full snapshots. Each call rewrites the next batch and returns the progress.
+impl Contract {
+    fn unsafe_store_rfp_snapshots(migrated_count: u64) -> StateVersion {
+        let ContractV14 { mut rfps, .. } = env::state_read().unwrap();
+        let end = rfps.len().min(migrated_count + MIGRATED_RFPS_PER_CALL);
+        for index in migrated_count..end {
+            let rfp: RFP = rfps.get(index).unwrap().into();
+            rfps.replace(index, &rfp.into());
+        }
+        StateVersion::V15 { done: end == rfps.len(), migrated_count: end }
+    }
+}
+
+// From ContractV15 to ContractV16
+impl Contract {
+    fn unsafe_add_rfp_evaluations() {
+        let ContractV14 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV16 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations: LookupMap::new(StorageKey::RFPEvaluations),
+        });
+    }
+}
+
+// From ContractV16 to ContractV17
+impl Contract {
+    fn unsafe_add_require_registered_proposal_labels() {
+        let ContractV16 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV17 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels: false,
+        });
+    }
+}
+
+// From ContractV17 to ContractV18
+impl Contract {
+    fn unsafe_add_access_control_audit_log() {
+        let ContractV17 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV18 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log: Vector::new(StorageKey::AccessControlAuditLog),
+        });
+    }
+}
+
+// From ContractV18 to ContractV19
+impl Contract {
+    fn unsafe_add_sensitive_actions() {
+        let ContractV18 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV19 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions: SensitiveActions::default(),
+        });
+    }
+}
+
+// From ContractV19 to ContractV20
+impl Contract {
+    fn unsafe_add_moderators_team() {
+        let ContractV19 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV20 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team: DEFAULT_MODERATORS_TEAM.to_string(),
+        });
+    }
+}
+
+// From ContractV20 to ContractV21
+impl Contract {
+    fn unsafe_add_owner() {
+        let ContractV20 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV21 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner: env::current_account_id(),
+            proposed_owner: None,
+        });
+    }
+}
+
+// From ContractV21 to ContractV22
+impl Contract {
+    fn unsafe_add_community_members() {
+        let ContractV21 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV22 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members: LookupMap::new(StorageKey::CommunityMembers),
+            account_communities: LookupMap::new(StorageKey::AccountCommunities),
+        });
+    }
+}
+
+// From ContractV22 to ContractV23
+impl Contract {
+    fn unsafe_add_community_admin_invitations() {
+        let ContractV22 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV23 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations: LookupMap::new(StorageKey::CommunityAdminInvitations),
+        });
+    }
+}
+
+// From ContractV23 to ContractV24
+impl Contract {
+    fn unsafe_add_archived_communities() {
+        let ContractV23 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV24 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities: UnorderedMap::new(StorageKey::ArchivedCommunities),
+        });
+    }
+}
+
+// From ContractV24 to ContractV25
+impl Contract {
+    fn unsafe_add_community_aliases() {
+        let ContractV24 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV25 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+            community_aliases: UnorderedMap::new(StorageKey::CommunityAliases),
+        });
+    }
+}
+
+// From ContractV25 to ContractV26
+impl Contract {
+    fn unsafe_add_community_search_indexes() {
+        let ContractV25 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+            community_aliases,
+        } = env::state_read().unwrap();
+
+        let mut tag_to_communities = UnorderedMap::new(StorageKey::TagToCommunities);
+        let mut community_search_index = TreeMap::new(StorageKey::CommunitySearchIndex);
+        for (handle, community) in communities.iter() {
+            let mut handles: HashSet<CommunityHandle> =
+                tag_to_communities.get(&community.tag).unwrap_or_default();
+            handles.insert(handle.clone());
+            tag_to_communities.insert(&community.tag, &handles);
+
+            for term in community_search_terms(&handle, &community.name) {
+                let mut handles: HashSet<CommunityHandle> =
+                    community_search_index.get(&term).unwrap_or_default();
+                handles.insert(handle.clone());
+                community_search_index.insert(&term, &handles);
+            }
+        }
+
+        env::state_write(&ContractV26 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+            community_aliases,
+            tag_to_communities,
+            community_search_index,
+        });
+    }
+}
+
+// From ContractV26 to ContractV27
+impl Contract {
+    fn unsafe_add_deprecated_labels() {
+        let ContractV26 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+            community_aliases,
+            tag_to_communities,
+            community_search_index,
+        } = env::state_read().unwrap();
+
+        env::state_write(&ContractV27 {
+            posts,
+            post_to_parent,
+            post_to_children,
+            label_to_posts,
+            access_control,
+            authors,
+            proposals,
+            label_to_proposals,
+            author_proposals,
+            proposal_categories,
+            rfps,
+            label_to_rfps,
+            global_labels_info,
+            communities,
+            featured_communities,
+            available_addons,
+            change_log,
+            proposal_workflow,
+            proposal_timeline_history,
+            rfp_evaluations,
+            require_registered_proposal_labels,
+            access_control_audit_log,
+            sensitive_actions,
+            moderators_team,
+            owner,
+            proposed_owner,
+            community_members,
+            account_communities,
+            community_admin_invitations,
+            archived_communities,
+            community_aliases,
+            tag_to_communities,
+            community_search_index,
+            deprecated_labels: HashSet::new(),
+        });
+    }
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV13 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV14 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+}
+
+// ContractV15 has the same layout as ContractV14, only the RFPs inside of it were rewritten.
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV16 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV17 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV18 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV19 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV20 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV21 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV22 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV23 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV24 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+    pub archived_communities: UnorderedMap<CommunityHandle, CommunityArchive>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV25 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+    pub archived_communities: UnorderedMap<CommunityHandle, CommunityArchive>,
+    pub community_aliases: UnorderedMap<CommunityHandle, CommunityAlias>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV26 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+    pub archived_communities: UnorderedMap<CommunityHandle, CommunityArchive>,
+    pub community_aliases: UnorderedMap<CommunityHandle, CommunityAlias>,
+    pub tag_to_communities: UnorderedMap<String, HashSet<CommunityHandle>>,
+    pub community_search_index: TreeMap<String, HashSet<CommunityHandle>>,
+}
+
+#[near]
+#[derive(PanicOnDefault)]
+pub struct ContractV27 {
+    pub posts: Vector<VersionedPost>,
+    pub post_to_parent: LookupMap<PostId, PostId>,
+    pub post_to_children: LookupMap<PostId, Vec<PostId>>,
+    pub label_to_posts: UnorderedMap<String, HashSet<PostId>>,
+    pub access_control: AccessControl,
+    pub authors: UnorderedMap<AccountId, HashSet<PostId>>,
+    pub proposals: Vector<VersionedProposal>,
+    pub label_to_proposals: UnorderedMap<String, HashSet<ProposalId>>,
+    pub author_proposals: UnorderedMap<AccountId, HashSet<ProposalId>>,
+    pub proposal_categories: Vec<String>,
+    pub rfps: Vector<VersionedRFP>,
+    pub label_to_rfps: UnorderedMap<String, HashSet<RFPId>>,
+    pub global_labels_info: Lazy<HashMap<String, LabelInfo>>,
+    pub communities: UnorderedMap<CommunityHandle, CommunityV5>,
+    pub featured_communities: Vec<FeaturedCommunity>,
+    pub available_addons: UnorderedMap<AddOnId, AddOn>,
+    pub change_log: VecDeque<ChangeLog>,
+    pub proposal_workflow: Lazy<ProposalWorkflow>,
+    pub proposal_timeline_history: LookupMap<ProposalId, Vec<TimelineTransition>>,
+    pub rfp_evaluations: LookupMap<RFPId, RFPEvaluation>,
+    pub require_registered_proposal_labels: bool,
+    pub access_control_audit_log: Vector<AccessControlAuditEntry>,
+    pub sensitive_actions: SensitiveActions,
+    pub moderators_team: String,
+    pub owner: AccountId,
+    pub proposed_owner: Option<AccountId>,
+    pub community_members: LookupMap<CommunityHandle, UnorderedMap<AccountId, CommunityMembership>>,
+    pub account_communities: LookupMap<AccountId, HashSet<CommunityHandle>>,
+    pub community_admin_invitations:
+        LookupMap<CommunityHandle, HashMap<AccountId, CommunityAdminInvitation>>,
+    pub archived_communities: UnorderedMap<CommunityHandle, CommunityArchive>,
+    pub community_aliases: UnorderedMap<CommunityHandle, CommunityAlias>,
+    pub tag_to_communities: UnorderedMap<String, HashSet<CommunityHandle>>,
+    pub community_search_index: TreeMap<String, HashSet<CommunityHandle>>,
+    pub deprecated_labels: HashSet<String>,
+}
+
 #[near]
 #[derive(Debug)]
 pub(crate) enum StateVersion {
@@ -771,6 +2097,21 @@ pub(crate) enum StateVersion {
     V10,
     V11,
     V12,
+    V13,
+    V14,
+    V15 { done: bool, migrated_count: u64 },
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+    V21,
+    V22,
+    V23,
+    V24,
+    V25,
+    V26,
+    V27,
 }
 
 const VERSION_KEY: &[u8] = b"VERSION";
@@ -791,8 +2132,12 @@ pub(crate) fn state_version_write(version: &StateVersion) {
 
 #[near]
 impl Contract {
+    /// The owner can only upgrade once the state was migrated to the layout of the deployed code.
     pub fn unsafe_self_upgrade() {
-        near_sdk::assert_self();
+        if env::predecessor_account_id() != env::current_account_id() {
+            let contract: Contract = env::state_read().expect("Contract is not initialized");
+            contract.assert_owner();
+        }
 
         let contract = env::input().expect("No contract code is attached in input");
         Promise::new(env::current_account_id())
@@ -867,6 +2212,68 @@ impl Contract {
                 Contract::unsafe_add_change_log();
                 state_version_write(&StateVersion::V12);
             }
+            StateVersion::V12 => {
+                Contract::unsafe_add_proposal_workflow();
+                state_version_write(&StateVersion::V13);
+            }
+            StateVersion::V13 => {
+                Contract::unsafe_add_proposal_timeline_history();
+                state_version_write(&StateVersion::V14);
+            }
+            StateVersion::V14 => {
+                state_version_write(&StateVersion::V15 { done: false, migrated_count: 0 });
+            }
+            StateVersion::V15 { done: false, migrated_count } => {
+                state_version_write(&Contract::unsafe_store_rfp_snapshots(migrated_count));
+            }
+            StateVersion::V15 { done: true, .. } => {
+                Contract::unsafe_add_rfp_evaluations();
+                state_version_write(&StateVersion::V16);
+            }
+            StateVersion::V16 => {
+                Contract::unsafe_add_require_registered_proposal_labels();
+                state_version_write(&StateVersion::V17);
+            }
+            StateVersion::V17 => {
+                Contract::unsafe_add_access_control_audit_log();
+                state_version_write(&StateVersion::V18);
+            }
+            StateVersion::V18 => {
+                Contract::unsafe_add_sensitive_actions();
+                state_version_write(&StateVersion::V19);
+            }
+            StateVersion::V19 => {
+                Contract::unsafe_add_moderators_team();
+                state_version_write(&StateVersion::V20);
+            }
+            StateVersion::V20 => {
+                Contract::unsafe_add_owner();
+                state_version_write(&StateVersion::V21);
+            }
+            StateVersion::V21 => {
+                Contract::unsafe_add_community_members();
+                state_version_write(&StateVersion::V22);
+            }
+            StateVersion::V22 => {
+                Contract::unsafe_add_community_admin_invitations();
+                state_version_write(&StateVersion::V23);
+            }
+            StateVersion::V23 => {
+                Contract::unsafe_add_archived_communities();
+                state_version_write(&StateVersion::V24);
+            }
+            StateVersion::V24 => {
+                Contract::unsafe_add_community_aliases();
+                state_version_write(&StateVersion::V25);
+            }
+            StateVersion::V25 => {
+                Contract::unsafe_add_community_search_indexes();
+                state_version_write(&StateVersion::V26);
+            }
+            StateVersion::V26 => {
+                Contract::unsafe_add_deprecated_labels();
+                state_version_write(&StateVersion::V27);
+            }
             _ => {
                 return Contract::migration_done();
             }
@@ -0,0 +1,46 @@
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId};
+
+impl Contract {
+    /// The owner has the admin powers. The contract account keeps them too, so that the
+    /// contract can call itself, e.g. to migrate after an upgrade.
+    pub(crate) fn is_owner(&self, account_id: &AccountId) -> bool {
+        account_id == &self.owner || account_id == &env::current_account_id()
+    }
+
+    pub(crate) fn assert_owner(&self) {
+        require!(
+            self.is_owner(&env::predecessor_account_id()),
+            "Only the owner can call this method"
+        );
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    pub fn get_proposed_owner(&self) -> Option<AccountId> {
+        self.proposed_owner.clone()
+    }
+
+    /// The proposed owner becomes the owner once it calls `accept_owner`. Proposing `None`
+    /// withdraws the proposal.
+    pub fn propose_owner(&mut self, owner: Option<AccountId>) {
+        self.assert_owner();
+        self.proposed_owner = owner;
+    }
+
+    pub fn accept_owner(&mut self) {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.proposed_owner.as_ref() == Some(&account_id),
+            "Only the proposed owner can accept the ownership"
+        );
+        self.owner = account_id;
+        self.proposed_owner = None;
+    }
+}
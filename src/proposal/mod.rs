@@ -1,15 +1,20 @@
 pub mod repost;
 pub mod timeline;
+pub mod workflow;
 
 use std::collections::HashSet;
 
-use self::timeline::{TimelineStatusV1, VersionedTimelineStatus};
+use self::timeline::{
+    TimelineStatusKind, TimelineStatusV1, TimelineTransition, VersionedTimelineStatus,
+};
 
 use crate::changelog::ChangeLogType;
+use crate::labels::assert_labels_not_rules;
 use crate::str_serializers::*;
 use crate::Contract;
 use crate::{notify::get_text_mentions, rfp::RFPId};
 
+use near_sdk::json_types::U64;
 use near_sdk::{env, near, require, AccountId, BlockHeight, Timestamp};
 
 pub type ProposalId = u32;
@@ -46,6 +51,20 @@ impl From<VersionedProposal> for Proposal {
     }
 }
 
+impl Proposal {
+    /// Snapshots are indexed from the oldest one, the current snapshot goes last.
+    pub fn snapshot_at(&self, index: u32) -> &ProposalSnapshot {
+        let index = index as usize;
+        if index == self.snapshot_history.len() {
+            &self.snapshot
+        } else {
+            self.snapshot_history
+                .get(index)
+                .unwrap_or_else(|| panic!("Snapshot {} of proposal {} not found", index, self.id))
+        }
+    }
+}
+
 impl From<Proposal> for VersionedProposal {
     fn from(p: Proposal) -> Self {
         VersionedProposal::V0(p)
@@ -128,6 +147,73 @@ pub struct ProposalBodyV2 {
     pub linked_rfp: Option<RFPId>,
 }
 
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct ProposalBodyV3 {
+    pub name: String,
+    pub category: String,
+    pub summary: String,
+    pub description: String,
+    pub linked_proposals: Vec<ProposalId>,
+    pub requested_sponsorship_paid_in_currency: ProposalFundingCurrency,
+    pub receiver_account: AccountId,
+    pub requested_sponsor: AccountId,
+    pub supervisor: Option<AccountId>,
+    pub timeline: VersionedTimelineStatus,
+    pub linked_rfp: Option<RFPId>,
+    /// Ordered list of the tranches the sponsorship is paid in.
+    pub milestones: Vec<Milestone>,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct Milestone {
+    pub description: String,
+    #[serde(
+        serialize_with = "u32_dec_format::serialize",
+        deserialize_with = "u32_dec_format::deserialize"
+    )]
+    pub usd_amount: u32,
+    /// Zero for the milestones converted from the proposal versions without milestones.
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub due_date: Timestamp,
+    pub status: MilestoneStatus,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MilestoneStatus {
+    Pending,
+    Delivered,
+    Funded { payouts: Vec<String> },
+}
+
+impl MilestoneStatus {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, MilestoneStatus::Pending)
+    }
+
+    pub fn is_funded(&self) -> bool {
+        matches!(self, MilestoneStatus::Funded { .. })
+    }
+
+    /// Milestones only move forward: from pending to delivered and from either to funded.
+    pub fn can_change_to(&self, new_status: &MilestoneStatus) -> bool {
+        matches!(
+            (self, new_status),
+            (MilestoneStatus::Pending, MilestoneStatus::Delivered)
+                | (
+                    MilestoneStatus::Pending | MilestoneStatus::Delivered,
+                    MilestoneStatus::Funded { .. }
+                )
+        )
+    }
+}
+
 #[near(serializers=[borsh, json])]
 #[derive(Clone)]
 #[serde(tag = "proposal_body_version")]
@@ -135,6 +221,7 @@ pub enum VersionedProposalBody {
     V0(ProposalBodyV0),
     V1(ProposalBodyV1),
     V2(ProposalBodyV2),
+    V3(ProposalBodyV3),
 }
 
 impl From<ProposalBodyV0> for ProposalBodyV1 {
@@ -175,6 +262,59 @@ impl From<ProposalBodyV1> for ProposalBodyV2 {
     }
 }
 
+impl From<ProposalBodyV2> for ProposalBodyV3 {
+    fn from(v2: ProposalBodyV2) -> Self {
+        let status = if v2.timeline.clone().latest_version().is_funded() {
+            MilestoneStatus::Funded { payouts: v2.timeline.legacy_payouts().unwrap_or_default() }
+        } else {
+            MilestoneStatus::Pending
+        };
+        ProposalBodyV3 {
+            milestones: vec![Milestone {
+                description: v2.summary.clone(),
+                usd_amount: v2.requested_sponsorship_usd_amount,
+                due_date: 0,
+                status,
+            }],
+            name: v2.name,
+            category: v2.category,
+            summary: v2.summary,
+            description: v2.description,
+            linked_proposals: v2.linked_proposals,
+            requested_sponsorship_paid_in_currency: v2.requested_sponsorship_paid_in_currency,
+            receiver_account: v2.receiver_account,
+            requested_sponsor: v2.requested_sponsor,
+            supervisor: v2.supervisor,
+            timeline: v2.timeline.latest_version().into(),
+            linked_rfp: v2.linked_rfp,
+        }
+    }
+}
+
+/// The amounts of the milestones add up to the requested sponsorship. Milestone payouts are not
+/// carried over, the timeline is kept as it is.
+impl From<ProposalBodyV3> for ProposalBodyV2 {
+    fn from(v3: ProposalBodyV3) -> Self {
+        ProposalBodyV2 {
+            requested_sponsorship_usd_amount: v3
+                .requested_sponsorship_usd_amount()
+                .try_into()
+                .unwrap_or(u32::MAX),
+            name: v3.name,
+            category: v3.category,
+            summary: v3.summary,
+            description: v3.description,
+            linked_proposals: v3.linked_proposals,
+            requested_sponsorship_paid_in_currency: v3.requested_sponsorship_paid_in_currency,
+            receiver_account: v3.receiver_account,
+            requested_sponsor: v3.requested_sponsor,
+            supervisor: v3.supervisor,
+            timeline: v3.timeline,
+            linked_rfp: v3.linked_rfp,
+        }
+    }
+}
+
 impl From<VersionedProposalBody> for ProposalBodyV0 {
     fn from(solution: VersionedProposalBody) -> Self {
         match solution {
@@ -203,6 +343,19 @@ impl From<VersionedProposalBody> for ProposalBodyV2 {
             }
             VersionedProposalBody::V1(v1) => v1.into(),
             VersionedProposalBody::V2(v2) => v2,
+            VersionedProposalBody::V3(v3) => v3.into(),
+        }
+    }
+}
+
+impl From<VersionedProposalBody> for ProposalBodyV3 {
+    fn from(solution: VersionedProposalBody) -> Self {
+        match solution {
+            VersionedProposalBody::V3(v3) => v3,
+            older => {
+                let v2: ProposalBodyV2 = older.into();
+                v2.into()
+            }
         }
     }
 }
@@ -225,13 +378,59 @@ impl From<ProposalBodyV2> for VersionedProposalBody {
     }
 }
 
+impl From<ProposalBodyV3> for VersionedProposalBody {
+    fn from(p: ProposalBodyV3) -> Self {
+        VersionedProposalBody::V3(p)
+    }
+}
+
 impl VersionedProposalBody {
-    pub fn latest_version(self) -> ProposalBodyV2 {
+    pub fn latest_version(self) -> ProposalBodyV3 {
         self.into()
     }
+
+    pub fn is_latest_version(&self) -> bool {
+        matches!(self, VersionedProposalBody::V3(..))
+    }
+}
+
+impl ProposalBodyV3 {
+    /// Milestones are left as they are, see `Contract::record_legacy_payouts` for the payouts of
+    /// the older timeline version.
+    pub fn set_timeline(&mut self, timeline: VersionedTimelineStatus) {
+        self.timeline = timeline.latest_version().into();
+    }
+
+    pub fn requested_sponsorship_usd_amount(&self) -> u64 {
+        self.milestones.iter().map(|milestone| milestone.usd_amount as u64).sum()
+    }
+}
+
+/// Milestone statuses only change through `edit_proposal_milestone`. Milestones added by an edit
+/// start as pending, and the ones already delivered or funded can't be removed or get another
+/// amount.
+fn assert_milestone_statuses_kept(old_milestones: &[Milestone], new_milestones: &[Milestone]) {
+    for (index, milestone) in new_milestones.iter().enumerate() {
+        let old_status = old_milestones
+            .get(index)
+            .map_or(MilestoneStatus::Pending, |old_milestone| old_milestone.status.clone());
+        require!(
+            milestone.status == old_status,
+            "Milestone statuses can only be changed with edit_proposal_milestone"
+        );
+    }
+    for (index, old_milestone) in old_milestones.iter().enumerate() {
+        require!(
+            old_milestone.status.is_pending()
+                || new_milestones
+                    .get(index)
+                    .map_or(false, |milestone| milestone.usd_amount == old_milestone.usd_amount),
+            "Delivered and funded milestones can't be removed or change their amount"
+        );
+    }
 }
 
-pub fn get_subscribers(proposal_body: &ProposalBodyV2) -> Vec<String> {
+pub fn get_subscribers(proposal_body: &ProposalBodyV3) -> Vec<String> {
     let mut result = [
         get_text_mentions(proposal_body.description.as_str()),
         get_text_mentions(proposal_body.summary.as_str()),
@@ -265,6 +464,41 @@ pub enum ProposalFundingCurrency {
     OTHER,
 }
 
+/// Criteria for `get_proposals_paged`. Every criterion that is set must match.
+#[near(serializers=[json])]
+#[derive(Clone, Default)]
+pub struct ProposalFilter {
+    pub status: Option<TimelineStatusKind>,
+    pub category: Option<String>,
+    pub author_id: Option<AccountId>,
+    pub label: Option<String>,
+    pub linked_rfp: Option<RFPId>,
+    /// Inclusive bounds on the timestamp of the latest snapshot.
+    pub from_timestamp: Option<U64>,
+    pub to_timestamp: Option<U64>,
+}
+
+impl ProposalFilter {
+    pub fn matches(&self, proposal: &Proposal) -> bool {
+        let body = proposal.snapshot.body.clone().latest_version();
+        let timestamp = proposal.snapshot.timestamp;
+        self.status.map_or(true, |status| body.timeline.clone().latest_version().kind() == status)
+            && self.category.as_ref().map_or(true, |category| &body.category == category)
+            && self.author_id.as_ref().map_or(true, |author_id| &proposal.author_id == author_id)
+            && self.label.as_ref().map_or(true, |label| proposal.snapshot.labels.contains(label))
+            && self.linked_rfp.map_or(true, |rfp_id| body.linked_rfp == Some(rfp_id))
+            && self.from_timestamp.map_or(true, |from| timestamp >= from.0)
+            && self.to_timestamp.map_or(true, |to| timestamp <= to.0)
+    }
+}
+
+#[near(serializers=[json])]
+pub struct ProposalsPage {
+    pub proposals: Vec<VersionedProposal>,
+    /// Index to pass as `from_index` to fetch the next page, `None` once everything was scanned.
+    pub next_index: Option<ProposalId>,
+}
+
 impl Contract {
     pub(crate) fn update_proposal_labels(
         &mut self,
@@ -290,6 +524,20 @@ impl Contract {
             self.is_allowed_to_edit_proposal(id, Option::None),
             "The account is not allowed to edit this proposal"
         );
+        self.edit_proposal_timeline_internal(id, body, labels, None)
+    }
+
+    /// Edits the proposal checking only that the account can make the status change, through a
+    /// role in the workflow or the permission to override its timeline. It is enough for the
+    /// edits that change nothing but the timeline. A status change is recorded in the timeline
+    /// history together with the optional reason.
+    pub(crate) fn edit_proposal_timeline_internal(
+        &mut self,
+        id: ProposalId,
+        body: VersionedProposalBody,
+        labels: HashSet<String>,
+        reason: Option<String>,
+    ) -> ProposalId {
         let editor_id = env::predecessor_account_id();
         let mut proposal: Proposal = self
             .proposals
@@ -300,19 +548,45 @@ impl Contract {
         let proposal_body = body.clone().latest_version();
 
         let old_body = proposal.snapshot.body.clone();
+        require!(
+            body.is_latest_version() || !old_body.is_latest_version(),
+            "The proposal has milestones, it can only be edited with a V3 body"
+        );
+        require!(!proposal_body.milestones.is_empty(), "Proposal must have at least one milestone");
+        assert_milestone_statuses_kept(
+            &old_body.clone().latest_version().milestones,
+            &proposal_body.milestones,
+        );
         let labels =
             self.update_and_check_rfp_link(id, body.clone(), Some(old_body.clone()), labels);
 
         let current_timeline = old_body.latest_version().timeline.latest_version();
         let new_timeline = proposal_body.timeline.latest_version();
+        if let Some(rfp_id) = proposal_body.linked_rfp.filter(|_| !new_timeline.was_approved()) {
+            self.assert_proposal_not_selected_in_rfp(rfp_id, id);
+        }
+
+        require!(
+            self.is_allowed_to_change_proposal_status(
+                &editor_id,
+                &proposal,
+                current_timeline.kind(),
+                new_timeline.kind()
+            ),
+            format!(
+                "This account is not allowed to change proposal status from {:?} to {:?}",
+                current_timeline.kind(),
+                new_timeline.kind()
+            )
+        );
 
         require!(
             self.has_moderator(editor_id.clone())
-                || editor_id.clone() == env::current_account_id()
-                || current_timeline.is_draft()
-                    && (new_timeline.is_empty_review() || new_timeline.is_draft())
-                || current_timeline.can_be_cancelled() && new_timeline.is_cancelled(),
-            "This account is only allowed to change proposal status from DRAFT to REVIEW"
+                || self.is_allowed_to_override_proposal_timeline(&editor_id, &proposal)
+                || !current_timeline.is_draft()
+                || !new_timeline.is_review()
+                || new_timeline.is_empty_review(),
+            "Only moderators can submit a proposal for review with the review already started"
         );
 
         require!(
@@ -320,6 +594,12 @@ impl Contract {
             "You can't change the timeline of the proposal to this status without adding a supervisor"
         );
 
+        require!(
+            !new_timeline.is_funded()
+                || proposal_body.milestones.iter().all(|milestone| milestone.status.is_funded()),
+            "You can't change the timeline of the proposal to FUNDED before all of its milestones are funded"
+        );
+
         require!(self.proposal_categories.contains(&proposal_body.category), "Unknown category");
 
         let old_snapshot = proposal.snapshot.clone();
@@ -354,6 +634,12 @@ impl Contract {
             ),
             "Not allowed to add these labels"
         );
+        self.assert_proposal_labels_registered(&labels_to_add);
+        // Labels inherited from the linked RFP are already on it.
+        if proposal_body.linked_rfp.is_none() {
+            assert_labels_not_rules(&labels_to_add);
+            self.assert_labels_not_deprecated(&labels_to_add);
+        }
 
         for label_to_remove in labels_to_remove {
             let mut proposals = self.label_to_proposals.get(&label_to_remove).unwrap();
@@ -367,6 +653,98 @@ impl Contract {
             self.label_to_proposals.insert(&label_to_add, &proposals);
         }
 
+        if current_timeline.kind() != new_timeline.kind() {
+            let mut history = self.proposal_timeline_history.get(&id).unwrap_or_default();
+            history.push(TimelineTransition {
+                from: current_timeline.kind(),
+                to: new_timeline.kind(),
+                actor: editor_id,
+                block_height: env::block_height(),
+                reason,
+            });
+            self.proposal_timeline_history.insert(&id, &history);
+        }
+
+        self.add_change_log(ChangeLogType::Proposal(id));
+
+        crate::notify::notify_edit_proposal(id, proposal_author);
+        id
+    }
+
+    /// A funded timeline of the older version carries payouts. They are recorded on the last
+    /// milestone, as this is where they end up for the proposals converted to milestones, and go
+    /// through the same checks as `edit_proposal_milestone`.
+    pub(crate) fn record_legacy_payouts(
+        &mut self,
+        id: ProposalId,
+        timeline: &VersionedTimelineStatus,
+    ) {
+        let Some(payouts) = timeline.legacy_payouts() else {
+            return;
+        };
+        let proposal: Proposal = self
+            .proposals
+            .get(id.into())
+            .unwrap_or_else(|| panic!("Proposal id {} not found", id))
+            .into();
+        let milestones = proposal.snapshot.body.latest_version().milestones;
+        if let Some(milestone) = milestones.last() {
+            if !milestone.status.is_funded() {
+                let index = (milestones.len() - 1) as u32;
+                self.edit_proposal_milestone_internal(
+                    id,
+                    index,
+                    MilestoneStatus::Funded { payouts },
+                );
+            }
+        }
+    }
+
+    pub(crate) fn edit_proposal_milestone_internal(
+        &mut self,
+        id: ProposalId,
+        milestone_index: u32,
+        status: MilestoneStatus,
+    ) -> ProposalId {
+        let editor_id = env::predecessor_account_id();
+        let mut proposal: Proposal = self
+            .proposals
+            .get(id.into())
+            .unwrap_or_else(|| panic!("Proposal id {} not found", id))
+            .into();
+        let mut body = proposal.snapshot.body.clone().latest_version();
+
+        let is_moderator = self.has_moderator(editor_id.clone()) || self.is_owner(&editor_id);
+        require!(
+            is_moderator || body.supervisor.as_ref() == Some(&editor_id),
+            "Only the supervisor and moderators can update proposal milestones"
+        );
+        require!(
+            body.timeline.clone().latest_version().was_approved(),
+            "Milestones can only be updated once the proposal is approved"
+        );
+
+        let milestone = body
+            .milestones
+            .get_mut(milestone_index as usize)
+            .unwrap_or_else(|| panic!("Milestone {} not found", milestone_index));
+        require!(
+            is_moderator || milestone.status.can_change_to(&status),
+            "The milestone cannot be moved back to a previous status"
+        );
+        milestone.status = status;
+
+        let old_snapshot = proposal.snapshot.clone();
+        proposal.snapshot = ProposalSnapshot {
+            editor_id,
+            timestamp: env::block_timestamp(),
+            labels: old_snapshot.labels.clone(),
+            body: body.into(),
+        };
+        proposal.snapshot_history.push(old_snapshot);
+        let proposal_author = proposal.author_id.clone();
+        self.proposals.replace(id.into(), &proposal.into());
+
         self.add_change_log(ChangeLogType::Proposal(id));
 
         crate::notify::notify_edit_proposal(id, proposal_author);
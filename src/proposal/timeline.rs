@@ -1,6 +1,8 @@
-use near_sdk::near;
+use crate::str_serializers::*;
 
-pub type TimelineStatus = TimelineStatusV2;
+use near_sdk::{near, AccountId, BlockHeight};
+
+pub type TimelineStatus = TimelineStatusV3;
 type ReviewStatus = ReviewStatusV2;
 
 #[near(serializers=[borsh, json])]
@@ -22,6 +24,7 @@ pub enum TimelineStatusV1 {
 #[serde(tag = "timeline_version")]
 pub enum VersionedTimelineStatus {
     V1(TimelineStatusV2),
+    V2(TimelineStatusV3),
 }
 
 #[near(serializers=[borsh, json])]
@@ -38,6 +41,21 @@ pub enum TimelineStatusV2 {
     Cancelled(ReviewStatusV2),
 }
 
+/// Payouts are no longer part of the timeline, they are recorded on each proposal milestone.
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimelineStatusV3 {
+    Draft,
+    Review(ReviewStatusV2),
+    Approved(ReviewStatusV2),
+    Rejected(ReviewStatusV2),
+    ApprovedConditionally(ReviewStatusV2),
+    PaymentProcessing(PaymentProcessingStatusV2),
+    Funded(FundedStatusV3),
+    Cancelled(ReviewStatusV2),
+}
+
 fn convert_review_status_to_v1(review_status: ReviewStatusV1, kyc_verified: bool) -> ReviewStatusV2 {
     ReviewStatusV2 {
         sponsor_requested_review: review_status.sponsor_requested_review,
@@ -86,16 +104,59 @@ impl From<TimelineStatusV1> for TimelineStatusV2 {
     }
 }
 
+impl From<FundedStatusV2> for FundedStatusV3 {
+    fn from(value: FundedStatusV2) -> Self {
+        FundedStatusV3 {
+            payment_processing_status: value.payment_processing_status,
+            trustees_released_payment: value.trustees_released_payment,
+        }
+    }
+}
+
+impl From<TimelineStatusV2> for TimelineStatusV3 {
+    fn from(value: TimelineStatusV2) -> Self {
+        match value {
+            TimelineStatusV2::Draft => TimelineStatusV3::Draft,
+            TimelineStatusV2::Review(review_status) => TimelineStatusV3::Review(review_status),
+            TimelineStatusV2::Approved(review_status) => TimelineStatusV3::Approved(review_status),
+            TimelineStatusV2::Rejected(review_status) => TimelineStatusV3::Rejected(review_status),
+            TimelineStatusV2::ApprovedConditionally(review_status) => {
+                TimelineStatusV3::ApprovedConditionally(review_status)
+            }
+            TimelineStatusV2::PaymentProcessing(payment_processing_status) => {
+                TimelineStatusV3::PaymentProcessing(payment_processing_status)
+            }
+            TimelineStatusV2::Funded(funded_status) => {
+                TimelineStatusV3::Funded(funded_status.into())
+            }
+            TimelineStatusV2::Cancelled(review_status) => {
+                TimelineStatusV3::Cancelled(review_status)
+            }
+        }
+    }
+}
+
 impl VersionedTimelineStatus {
     pub fn latest_version(self) -> TimelineStatus {
         self.into()
     }
+
+    /// Payouts of a funded timeline of the version that still kept them.
+    pub fn legacy_payouts(&self) -> Option<Vec<String>> {
+        match self {
+            VersionedTimelineStatus::V1(TimelineStatusV2::Funded(funded_status)) => {
+                Some(funded_status.payouts.clone())
+            }
+            _ => None,
+        }
+    }
 }
 
-impl From<VersionedTimelineStatus> for TimelineStatusV2 {
+impl From<VersionedTimelineStatus> for TimelineStatusV3 {
     fn from(value: VersionedTimelineStatus) -> Self {
         match value {
-            VersionedTimelineStatus::V1(v1) => v1,
+            VersionedTimelineStatus::V1(v1) => v1.into(),
+            VersionedTimelineStatus::V2(v2) => v2,
         }
     }
 }
@@ -106,13 +167,62 @@ impl From<TimelineStatusV2> for VersionedTimelineStatus {
     }
 }
 
+impl From<TimelineStatusV3> for VersionedTimelineStatus {
+    fn from(value: TimelineStatusV3) -> Self {
+        VersionedTimelineStatus::V2(value)
+    }
+}
+
 impl From<TimelineStatusV1> for VersionedTimelineStatus {
     fn from(value: TimelineStatusV1) -> Self {
         VersionedTimelineStatus::V1(value.into())
     }
 }
 
+/// The status of a proposal timeline without any of its attached review details.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TimelineStatusKind {
+    Draft,
+    Review,
+    Approved,
+    Rejected,
+    ApprovedConditionally,
+    PaymentProcessing,
+    Funded,
+    Cancelled,
+}
+
+/// A single change of the proposal status, kept separately from the snapshots.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelineTransition {
+    pub from: TimelineStatusKind,
+    pub to: TimelineStatusKind,
+    pub actor: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub block_height: BlockHeight,
+    pub reason: Option<String>,
+}
+
 impl TimelineStatus {
+    pub fn kind(&self) -> TimelineStatusKind {
+        match self {
+            TimelineStatus::Draft => TimelineStatusKind::Draft,
+            TimelineStatus::Review(..) => TimelineStatusKind::Review,
+            TimelineStatus::Approved(..) => TimelineStatusKind::Approved,
+            TimelineStatus::Rejected(..) => TimelineStatusKind::Rejected,
+            TimelineStatus::ApprovedConditionally(..) => TimelineStatusKind::ApprovedConditionally,
+            TimelineStatus::PaymentProcessing(..) => TimelineStatusKind::PaymentProcessing,
+            TimelineStatus::Funded(..) => TimelineStatusKind::Funded,
+            TimelineStatus::Cancelled(..) => TimelineStatusKind::Cancelled,
+        }
+    }
+
     pub fn is_draft(&self) -> bool {
         matches!(self, TimelineStatus::Draft)
     }
@@ -135,6 +245,10 @@ impl TimelineStatus {
         matches!(self, TimelineStatus::Cancelled(..))
     }
 
+    pub fn is_funded(&self) -> bool {
+        matches!(self, TimelineStatus::Funded(..))
+    }
+
     pub fn can_be_cancelled(&self) -> bool {
         match self {
             TimelineStatus::Draft => true,
@@ -229,3 +343,11 @@ pub struct FundedStatusV2 {
     trustees_released_payment: bool,
     payouts: Vec<String>,
 }
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct FundedStatusV3 {
+    #[serde(flatten)]
+    payment_processing_status: PaymentProcessingStatusV2,
+    trustees_released_payment: bool,
+}
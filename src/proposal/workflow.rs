@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use crate::access_control::members::{ActionType, Member};
+use crate::proposal::timeline::TimelineStatusKind;
+use crate::proposal::{Proposal, ProposalId};
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId};
+
+/// Who is allowed to move a proposal between two timeline statuses.
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum ProposalRole {
+    Author,
+    Supervisor,
+    RequestedSponsor,
+    Moderators,
+    /// Any member of the access control team, directly or through its parents.
+    Team(String),
+    /// Anyone allowed to edit the proposal, see `is_allowed_to_edit_proposal`.
+    Editors,
+}
+
+const AUTHOR: &str = "author";
+const SUPERVISOR: &str = "supervisor";
+const REQUESTED_SPONSOR: &str = "requested_sponsor";
+const MODERATORS: &str = "moderators";
+const EDITORS: &str = "editors";
+/// JSON string representation prefix of `ProposalRole::Team` variant, same as for `Member::Team`.
+const TEAM: &str = "team:";
+
+impl TryFrom<String> for ProposalRole {
+    type Error = String;
+
+    fn try_from(full_str: String) -> Result<Self, Self::Error> {
+        match full_str.as_str() {
+            AUTHOR => Ok(ProposalRole::Author),
+            SUPERVISOR => Ok(ProposalRole::Supervisor),
+            REQUESTED_SPONSOR => Ok(ProposalRole::RequestedSponsor),
+            MODERATORS => Ok(ProposalRole::Moderators),
+            EDITORS => Ok(ProposalRole::Editors),
+            _ => full_str
+                .strip_prefix(TEAM)
+                .map(|team| ProposalRole::Team(team.to_string()))
+                .ok_or_else(|| format!("Unknown proposal role `{}`", full_str)),
+        }
+    }
+}
+
+impl From<ProposalRole> for String {
+    fn from(role: ProposalRole) -> Self {
+        match role {
+            ProposalRole::Author => AUTHOR.to_string(),
+            ProposalRole::Supervisor => SUPERVISOR.to_string(),
+            ProposalRole::RequestedSponsor => REQUESTED_SPONSOR.to_string(),
+            ProposalRole::Moderators => MODERATORS.to_string(),
+            ProposalRole::Team(team) => format!("{}{}", TEAM, team),
+            ProposalRole::Editors => EDITORS.to_string(),
+        }
+    }
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposalTransition {
+    pub from: TimelineStatusKind,
+    pub to: TimelineStatusKind,
+    pub roles: HashSet<ProposalRole>,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposalWorkflow {
+    pub transitions: Vec<ProposalTransition>,
+}
+
+const ALL_STATUSES: [TimelineStatusKind; 8] = [
+    TimelineStatusKind::Draft,
+    TimelineStatusKind::Review,
+    TimelineStatusKind::Approved,
+    TimelineStatusKind::Rejected,
+    TimelineStatusKind::ApprovedConditionally,
+    TimelineStatusKind::PaymentProcessing,
+    TimelineStatusKind::Funded,
+    TimelineStatusKind::Cancelled,
+];
+
+impl Default for ProposalWorkflow {
+    /// Authors and everyone else allowed to edit the proposal can keep editing a draft, submit it
+    /// for review and cancel it until it is reviewed. Moderators can make any move.
+    fn default() -> Self {
+        let editor_moves = [
+            (TimelineStatusKind::Draft, TimelineStatusKind::Draft),
+            (TimelineStatusKind::Draft, TimelineStatusKind::Review),
+            (TimelineStatusKind::Draft, TimelineStatusKind::Cancelled),
+            (TimelineStatusKind::Review, TimelineStatusKind::Cancelled),
+        ];
+        let mut transitions = vec![];
+        for from in ALL_STATUSES {
+            for to in ALL_STATUSES {
+                let mut roles = HashSet::from([ProposalRole::Moderators]);
+                if editor_moves.contains(&(from, to)) {
+                    roles.insert(ProposalRole::Author);
+                    roles.insert(ProposalRole::Editors);
+                }
+                transitions.push(ProposalTransition { from, to, roles });
+            }
+        }
+        ProposalWorkflow { transitions }
+    }
+}
+
+impl ProposalWorkflow {
+    pub fn allowed_roles(
+        &self,
+        from: TimelineStatusKind,
+        to: TimelineStatusKind,
+    ) -> impl Iterator<Item = &ProposalRole> {
+        self.transitions
+            .iter()
+            .filter(move |transition| transition.from == from && transition.to == to)
+            .flat_map(|transition| transition.roles.iter())
+    }
+}
+
+impl Contract {
+    fn has_proposal_role(
+        &self,
+        account: &AccountId,
+        proposal: &Proposal,
+        role: &ProposalRole,
+    ) -> bool {
+        let body = proposal.snapshot.body.clone().latest_version();
+        match role {
+            ProposalRole::Author => &proposal.author_id == account,
+            ProposalRole::Supervisor => body.supervisor.as_ref() == Some(account),
+            ProposalRole::RequestedSponsor => &body.requested_sponsor == account,
+            ProposalRole::Moderators => self.has_moderator(account.clone()),
+            ProposalRole::Team(team) => self
+                .access_control
+                .members_list
+                .is_member_of(&Member::Account(account.clone()), &Member::Team(team.clone())),
+            ProposalRole::Editors => {
+                self.is_allowed_to_edit_proposal(proposal.id, Some(account.clone()))
+            }
+        }
+    }
+
+    /// Whether the account can change the timeline of the proposal regardless of the workflow.
+    /// Moderators are not included, they follow the workflow like everyone else.
+    pub(crate) fn is_allowed_to_override_proposal_timeline(
+        &self,
+        account: &AccountId,
+        proposal: &Proposal,
+    ) -> bool {
+        self.is_owner(account)
+            || self.is_granted(
+                account,
+                ActionType::ChangeProposalTimeline,
+                proposal.snapshot.labels.iter().cloned().collect(),
+            )
+    }
+
+    /// The roles of the transition are enough on their own, e.g. a supervisor can reject a
+    /// proposal they are not allowed to edit. Edits of the rest of the proposal are checked
+    /// separately.
+    pub(crate) fn is_allowed_to_change_proposal_status(
+        &self,
+        account: &AccountId,
+        proposal: &Proposal,
+        from: TimelineStatusKind,
+        to: TimelineStatusKind,
+    ) -> bool {
+        self.is_allowed_to_override_proposal_timeline(account, proposal)
+            || self
+                .proposal_workflow
+                .allowed_roles(from, to)
+                .any(|role| self.has_proposal_role(account, proposal, role))
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_proposal_workflow(&self) -> &ProposalWorkflow {
+        &self.proposal_workflow
+    }
+
+    pub fn set_proposal_workflow(&mut self, workflow: ProposalWorkflow) {
+        require!(
+            self.has_moderator(env::predecessor_account_id())
+                || self.is_owner(&env::predecessor_account_id()),
+            "Only the admin and moderators can set the proposal workflow"
+        );
+        let mut moves = HashSet::new();
+        for transition in &workflow.transitions {
+            require!(
+                moves.insert((transition.from, transition.to)),
+                format!(
+                    "The workflow has more than one transition from {:?} to {:?}",
+                    transition.from, transition.to
+                )
+            );
+            for role in &transition.roles {
+                if let ProposalRole::Team(team) = role {
+                    require!(
+                        self.access_control
+                            .members_list
+                            .members
+                            .contains_key(&Member::Team(team.clone())),
+                        format!("Team {} does not exist", team)
+                    );
+                }
+            }
+        }
+        self.proposal_workflow.set(workflow);
+    }
+
+    /// Statuses the account can move the proposal to from its current status.
+    pub fn get_allowed_proposal_transitions(
+        &self,
+        proposal_id: ProposalId,
+        account_id: AccountId,
+    ) -> Vec<TimelineStatusKind> {
+        let proposal: Proposal = self.get_proposal(proposal_id).into();
+        let from = proposal.snapshot.body.clone().latest_version().timeline.latest_version().kind();
+        let mut result: Vec<_> = self
+            .proposal_workflow
+            .transitions
+            .iter()
+            .filter(|transition| transition.from == from)
+            .map(|transition| transition.to)
+            .filter(|to| {
+                self.is_allowed_to_change_proposal_status(&account_id, &proposal, from, *to)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        result.sort();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proposal::timeline::TimelineStatusKind;
+    use crate::proposal::workflow::{ProposalRole, ProposalWorkflow};
+    use near_sdk::serde_json;
+
+    #[test]
+    fn role_serialization() {
+        let roles = [
+            (ProposalRole::Author, "author"),
+            (ProposalRole::Supervisor, "supervisor"),
+            (ProposalRole::RequestedSponsor, "requested_sponsor"),
+            (ProposalRole::Moderators, "moderators"),
+            (ProposalRole::Team("funding".to_string()), "team:funding"),
+            (ProposalRole::Editors, "editors"),
+        ];
+        for (role, json) in roles {
+            assert_eq!(serde_json::to_value(&role).unwrap(), serde_json::json!(json));
+            assert_eq!(
+                serde_json::from_value::<ProposalRole>(serde_json::json!(json)).unwrap(),
+                role
+            );
+        }
+        assert!(serde_json::from_value::<ProposalRole>(serde_json::json!("reviewer")).is_err());
+    }
+
+    #[test]
+    fn default_workflow() {
+        let workflow = ProposalWorkflow::default();
+        let roles = |from, to| workflow.allowed_roles(from, to).cloned().collect::<Vec<_>>();
+
+        let draft_to_review = roles(TimelineStatusKind::Draft, TimelineStatusKind::Review);
+        assert!(draft_to_review.contains(&ProposalRole::Author));
+        assert!(draft_to_review.contains(&ProposalRole::Editors));
+        assert!(draft_to_review.contains(&ProposalRole::Moderators));
+
+        let review_to_approved = roles(TimelineStatusKind::Review, TimelineStatusKind::Approved);
+        assert_eq!(review_to_approved, vec![ProposalRole::Moderators]);
+
+        let approved_to_cancelled =
+            roles(TimelineStatusKind::Approved, TimelineStatusKind::Cancelled);
+        assert_eq!(approved_to_cancelled, vec![ProposalRole::Moderators]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::access_control::members::Member;
+use crate::proposal::ProposalId;
+use crate::rfp::{RFPId, RFP};
+use crate::str_serializers::*;
+use crate::{Contract, ContractExt};
+
+use near_sdk::{env, near, require, AccountId};
+
+/// The highest score a committee member can give for a single criterion.
+pub const MAX_CRITERION_SCORE: u32 = 10;
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationCriterion {
+    pub name: String,
+    pub weight: u32,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RFPEvaluation {
+    pub criteria: Vec<EvaluationCriterion>,
+    /// Either a single account or an access control team.
+    pub committee: Member,
+    /// When set, the RFP can only move to Proposal Selected with the top-ranked proposal approved,
+    /// unless an override reason is recorded.
+    pub require_top_ranked_selection: bool,
+    pub selection_override_reason: Option<String>,
+    /// Scores per criterion given by each committee member to each linked proposal.
+    pub scorecards: HashMap<ProposalId, HashMap<AccountId, Vec<u32>>>,
+}
+
+impl RFPEvaluation {
+    fn weighted_score(&self, scores: &[u32]) -> u64 {
+        self.criteria
+            .iter()
+            .zip(scores)
+            .map(|(criterion, score)| criterion.weight as u64 * *score as u64)
+            .sum()
+    }
+}
+
+#[near(serializers=[json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProposalRanking {
+    pub proposal_id: ProposalId,
+    /// Sum of the weighted scores of all scorecards, divide by `scorecards_count` to get the
+    /// average.
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub total_weighted_score: u64,
+    pub scorecards_count: u32,
+}
+
+impl ProposalRanking {
+    /// Higher average first, proposals without scorecards go last.
+    fn cmp_rank(&self, other: &Self) -> Ordering {
+        let left = self.total_weighted_score as u128 * other.scorecards_count.max(1) as u128;
+        let right = other.total_weighted_score as u128 * self.scorecards_count.max(1) as u128;
+        (other.scorecards_count > 0)
+            .cmp(&(self.scorecards_count > 0))
+            .then(right.cmp(&left))
+            .then(self.proposal_id.cmp(&other.proposal_id))
+    }
+}
+
+impl Contract {
+    fn get_rfp_evaluation_or_panic(&self, rfp_id: RFPId) -> RFPEvaluation {
+        self.rfp_evaluations
+            .get(&rfp_id)
+            .unwrap_or_else(|| panic!("RFP {} has no evaluation", rfp_id))
+    }
+
+    fn is_in_evaluation_committee(&self, account_id: &AccountId, committee: &Member) -> bool {
+        let member = Member::Account(account_id.clone());
+        &member == committee || self.access_control.members_list.is_member_of(&member, committee)
+    }
+
+    /// Checks the selection against the ranking when the RFP evaluation requires it.
+    pub(crate) fn assert_rfp_selection_follows_ranking(
+        &self,
+        rfp_id: RFPId,
+        selected_proposals: &HashSet<ProposalId>,
+    ) {
+        let Some(evaluation) = self.rfp_evaluations.get(&rfp_id) else {
+            return;
+        };
+        if !evaluation.require_top_ranked_selection
+            || evaluation.selection_override_reason.is_some()
+        {
+            return;
+        }
+        let top_ranked_selected = self
+            .get_rfp_ranking(rfp_id)
+            .first()
+            .filter(|ranking| ranking.scorecards_count > 0)
+            .map_or(false, |ranking| selected_proposals.contains(&ranking.proposal_id));
+        require!(
+            top_ranked_selected,
+            "Cannot change RFP status to Proposal Selected without the top-ranked proposal selected or an override reason recorded"
+        );
+    }
+}
+
+#[near]
+impl Contract {
+    pub fn get_rfp_evaluation(&self, rfp_id: RFPId) -> Option<RFPEvaluation> {
+        self.rfp_evaluations.get(&rfp_id)
+    }
+
+    /// Sets the rubric and the committee. They can't be changed once scoring has started.
+    pub fn set_rfp_evaluation(
+        &mut self,
+        rfp_id: RFPId,
+        criteria: Vec<EvaluationCriterion>,
+        committee: Member,
+        require_top_ranked_selection: bool,
+    ) {
+        require!(
+            self.is_allowed_to_write_rfps(env::predecessor_account_id()),
+            "The account is not allowed to edit RFPs"
+        );
+        self.load_rfp(rfp_id);
+        require!(!criteria.is_empty(), "Evaluation must have at least one criterion");
+        require!(
+            criteria.iter().all(|criterion| criterion.weight > 0),
+            "Evaluation criteria must have a positive weight"
+        );
+        let evaluation = self.rfp_evaluations.get(&rfp_id);
+        require!(
+            evaluation.as_ref().map_or(true, |evaluation| evaluation.scorecards.is_empty()),
+            "Cannot change the evaluation of an RFP after the scoring has started"
+        );
+        self.rfp_evaluations.insert(
+            &rfp_id,
+            &RFPEvaluation {
+                criteria,
+                committee,
+                require_top_ranked_selection,
+                selection_override_reason: evaluation
+                    .and_then(|evaluation| evaluation.selection_override_reason),
+                scorecards: HashMap::new(),
+            },
+        );
+    }
+
+    /// Replaces the scorecard the committee member gave to the proposal earlier, if any.
+    pub fn submit_rfp_scorecard(
+        &mut self,
+        rfp_id: RFPId,
+        proposal_id: ProposalId,
+        scores: Vec<u32>,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let mut evaluation = self.get_rfp_evaluation_or_panic(rfp_id);
+        require!(
+            self.is_in_evaluation_committee(&account_id, &evaluation.committee),
+            "Only the evaluation committee can score proposals"
+        );
+        let rfp: RFP = self.load_rfp(rfp_id);
+        require!(
+            rfp.snapshot.body.latest_version().timeline.is_evaluation(),
+            format!("The RFP {} is not in the Evaluation state", rfp_id)
+        );
+        require!(
+            rfp.snapshot.linked_proposals.contains(&proposal_id),
+            format!("Proposal {} is not linked to the RFP {}", proposal_id, rfp_id)
+        );
+        require!(
+            scores.len() == evaluation.criteria.len(),
+            "There must be exactly one score per evaluation criterion"
+        );
+        require!(
+            scores.iter().all(|score| *score <= MAX_CRITERION_SCORE),
+            format!("Scores can't be higher than {}", MAX_CRITERION_SCORE)
+        );
+        evaluation.scorecards.entry(proposal_id).or_default().insert(account_id, scores);
+        self.rfp_evaluations.insert(&rfp_id, &evaluation);
+    }
+
+    /// Records why the RFP may select a proposal other than the top-ranked one.
+    pub fn override_rfp_ranking(&mut self, rfp_id: RFPId, reason: String) {
+        require!(
+            self.is_allowed_to_write_rfps(env::predecessor_account_id()),
+            "The account is not allowed to edit RFPs"
+        );
+        require!(!reason.is_empty(), "The override reason can't be empty");
+        let mut evaluation = self.get_rfp_evaluation_or_panic(rfp_id);
+        evaluation.selection_override_reason = Some(reason);
+        self.rfp_evaluations.insert(&rfp_id, &evaluation);
+    }
+
+    /// Linked proposals ordered by their average weighted score, proposals without scorecards go
+    /// last.
+    pub fn get_rfp_ranking(&self, rfp_id: RFPId) -> Vec<ProposalRanking> {
+        let evaluation = self.get_rfp_evaluation_or_panic(rfp_id);
+        let mut ranking: Vec<_> = self
+            .get_linked_proposals_in_rfp(rfp_id)
+            .into_iter()
+            .map(|proposal_id| {
+                let scorecards = evaluation.scorecards.get(&proposal_id);
+                ProposalRanking {
+                    proposal_id,
+                    total_weighted_score: scorecards.map_or(0, |scorecards| {
+                        scorecards.values().map(|scores| evaluation.weighted_score(scores)).sum()
+                    }),
+                    scorecards_count: scorecards.map_or(0, |scorecards| scorecards.len() as u32),
+                }
+            })
+            .collect();
+        ranking.sort_by(ProposalRanking::cmp_rank);
+        ranking
+    }
+}
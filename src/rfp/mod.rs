@@ -1,3 +1,4 @@
+pub mod evaluation;
 pub mod repost;
 pub mod timeline;
 
@@ -6,8 +7,9 @@ use std::collections::HashSet;
 pub use self::timeline::TimelineStatus;
 
 use crate::changelog::ChangeLogType;
+use crate::labels::assert_labels_not_rules;
 use crate::notify::get_text_mentions;
-use crate::proposal::{Proposal, ProposalId, VersionedProposalBody};
+use crate::proposal::{Proposal, ProposalFundingCurrency, ProposalId, VersionedProposalBody};
 use crate::str_serializers::*;
 use crate::Contract;
 
@@ -21,12 +23,13 @@ type PostTag = String;
 #[derive(Clone)]
 #[serde(tag = "rfp_version")]
 pub enum VersionedRFP {
-    V0(RFP),
+    V0(RFPV0),
+    V1(RFP),
 }
 
 #[near(serializers=[borsh, json])]
 #[derive(Clone)]
-pub struct RFP {
+pub struct RFPV0 {
     pub id: RFPId,
     pub author_id: AccountId,
     #[serde(
@@ -40,17 +43,105 @@ pub struct RFP {
     pub snapshot_history: Vec<BlockHeight>,
 }
 
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct RFP {
+    pub id: RFPId,
+    pub author_id: AccountId,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub social_db_post_block_height: BlockHeight,
+    pub snapshot: RFPSnapshot,
+    // Excludes the current snapshot itself.
+    pub snapshot_history: Vec<RFPSnapshot>,
+    /// Block heights of the snapshots made before the full snapshots were kept.
+    pub legacy_snapshot_history: Vec<BlockHeight>,
+}
+
+impl From<RFPV0> for RFP {
+    fn from(v0: RFPV0) -> Self {
+        RFP {
+            id: v0.id,
+            author_id: v0.author_id,
+            social_db_post_block_height: v0.social_db_post_block_height,
+            snapshot: v0.snapshot,
+            snapshot_history: vec![],
+            legacy_snapshot_history: v0.snapshot_history,
+        }
+    }
+}
+
 impl From<VersionedRFP> for RFP {
     fn from(vp: VersionedRFP) -> Self {
         match vp {
-            VersionedRFP::V0(v0) => v0,
+            VersionedRFP::V0(v0) => v0.into(),
+            VersionedRFP::V1(v1) => v1,
+        }
+    }
+}
+
+impl RFP {
+    /// Snapshots are indexed from the oldest stored one, the current snapshot goes last. The
+    /// snapshots from `legacy_snapshot_history` are not stored, so they have no index.
+    pub fn snapshot_at(&self, index: u32) -> &RFPSnapshot {
+        let index = index as usize;
+        if index == self.snapshot_history.len() {
+            &self.snapshot
+        } else {
+            self.snapshot_history
+                .get(index)
+                .unwrap_or_else(|| panic!("Snapshot {} of RFP {} not found", index, self.id))
+        }
+    }
+
+    /// The RFP as it was at the given block height, with the history that preceded it. `None` if
+    /// the RFP was not created yet.
+    pub fn at_block_height(self, block_height: BlockHeight) -> Option<RFP> {
+        let mut rfp = self;
+        rfp.legacy_snapshot_history
+            .retain(|legacy_block_height| *legacy_block_height <= block_height);
+        while rfp.snapshot.block_height > block_height {
+            match rfp.snapshot_history.pop() {
+                Some(snapshot) => rfp.snapshot = snapshot,
+                None => {
+                    require!(
+                        rfp.legacy_snapshot_history.is_empty(),
+                        format!(
+                            "RFP {} at block height {} is not stored, it was only edited at block heights {:?}",
+                            rfp.id, block_height, rfp.legacy_snapshot_history
+                        )
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(rfp)
+    }
+}
+
+/// The view of `get_rfp` and `get_rfps`, which only list the block heights of the previous
+/// snapshots.
+impl From<RFP> for RFPV0 {
+    fn from(rfp: RFP) -> Self {
+        RFPV0 {
+            id: rfp.id,
+            author_id: rfp.author_id,
+            social_db_post_block_height: rfp.social_db_post_block_height,
+            snapshot: rfp.snapshot,
+            snapshot_history: rfp
+                .legacy_snapshot_history
+                .into_iter()
+                .chain(rfp.snapshot_history.iter().map(|snapshot| snapshot.block_height))
+                .collect(),
         }
     }
 }
 
 impl From<RFP> for VersionedRFP {
     fn from(p: RFP) -> Self {
-        VersionedRFP::V0(p)
+        VersionedRFP::V1(p)
     }
 }
 
@@ -88,17 +179,70 @@ pub struct RFPBodyV0 {
     pub submission_deadline: Timestamp,
 }
 
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct RFPBodyV1 {
+    pub name: String,
+    pub summary: String,
+    pub description: String,
+    pub timeline: TimelineStatus,
+    #[serde(
+        serialize_with = "u64_dec_format::serialize",
+        deserialize_with = "u64_dec_format::deserialize"
+    )]
+    pub submission_deadline: Timestamp,
+    /// The budget and its currency are not set for the RFPs created before they were added.
+    #[serde(
+        default,
+        serialize_with = "option_u32_dec_format::serialize",
+        deserialize_with = "option_u32_dec_format::deserialize"
+    )]
+    pub total_budget_usd_amount: Option<u32>,
+    #[serde(default)]
+    pub budget_paid_in_currency: Option<ProposalFundingCurrency>,
+    /// The winning proposals, only set once the RFP is in the Proposal Selected state.
+    pub selected_proposals: Vec<SelectedProposal>,
+}
+
+#[near(serializers=[borsh, json])]
+#[derive(Clone)]
+pub struct SelectedProposal {
+    pub proposal_id: ProposalId,
+    #[serde(
+        serialize_with = "u32_dec_format::serialize",
+        deserialize_with = "u32_dec_format::deserialize"
+    )]
+    pub allocated_usd_amount: u32,
+}
+
 #[near(serializers=[borsh, json])]
 #[derive(Clone)]
 #[serde(tag = "rfp_body_version")]
 pub enum VersionedRFPBody {
     V0(RFPBodyV0),
+    V1(RFPBodyV1),
+}
+
+impl From<RFPBodyV0> for RFPBodyV1 {
+    fn from(v0: RFPBodyV0) -> Self {
+        RFPBodyV1 {
+            name: v0.name,
+            summary: v0.summary,
+            description: v0.description,
+            timeline: v0.timeline,
+            submission_deadline: v0.submission_deadline,
+            total_budget_usd_amount: None,
+            budget_paid_in_currency: None,
+            selected_proposals: vec![],
+        }
+    }
 }
 
-impl From<VersionedRFPBody> for RFPBodyV0 {
+impl From<VersionedRFPBody> for RFPBodyV1 {
     fn from(solution: VersionedRFPBody) -> Self {
         match solution {
-            VersionedRFPBody::V0(v0) => v0,
+            VersionedRFPBody::V0(v0) => v0.into(),
+            VersionedRFPBody::V1(v1) => v1,
         }
     }
 }
@@ -109,13 +253,29 @@ impl From<RFPBodyV0> for VersionedRFPBody {
     }
 }
 
+impl From<RFPBodyV1> for VersionedRFPBody {
+    fn from(p: RFPBodyV1) -> Self {
+        VersionedRFPBody::V1(p)
+    }
+}
+
 impl VersionedRFPBody {
-    pub fn latest_version(self) -> RFPBodyV0 {
+    pub fn latest_version(self) -> RFPBodyV1 {
         self.into()
     }
 }
 
-pub fn get_subscribers(proposal_body: &RFPBodyV0) -> Vec<String> {
+impl RFPBodyV1 {
+    pub fn is_submission_deadline_passed(&self) -> bool {
+        env::block_timestamp() > self.submission_deadline
+    }
+
+    pub fn total_allocated_usd_amount(&self) -> u64 {
+        self.selected_proposals.iter().map(|selected| selected.allocated_usd_amount as u64).sum()
+    }
+}
+
+pub fn get_subscribers(proposal_body: &RFPBodyV1) -> Vec<String> {
     let result = [
         get_text_mentions(proposal_body.description.as_str()),
         get_text_mentions(proposal_body.summary.as_str()),
@@ -130,13 +290,14 @@ enum LinkedProposalChangeOperation {
 }
 
 impl Contract {
+    /// The RFP with its stored snapshots, unlike `get_rfp`.
+    pub(crate) fn load_rfp(&self, rfp_id: RFPId) -> RFP {
+        self.rfps.get(rfp_id.into()).unwrap_or_else(|| panic!("RFP id {} not found", rfp_id)).into()
+    }
+
     fn assert_can_link_unlink_rfp(&self, rfp_id: Option<RFPId>) {
         if let Some(rfp_id) = rfp_id {
-            let rfp: RFP = self
-                .rfps
-                .get(rfp_id.into())
-                .unwrap_or_else(|| panic!("RFP id {} not found", rfp_id))
-                .into();
+            let rfp = self.load_rfp(rfp_id);
             require!(
                 rfp.snapshot.body.latest_version().timeline.is_accepting_submissions() || self.is_allowed_to_write_rfps(env::predecessor_account_id()),
                 format!("The RFP {} is not in the Accepting Submissions state, so you can't link or unlink to this RFP", rfp_id)
@@ -144,6 +305,30 @@ impl Contract {
         }
     }
 
+    fn assert_rfp_submission_deadline_not_passed(&self, rfp_id: RFPId) {
+        let rfp: RFP = self.load_rfp(rfp_id);
+        require!(
+            !rfp.snapshot.body.latest_version().is_submission_deadline_passed()
+                || self.is_allowed_to_write_rfps(env::predecessor_account_id()),
+            format!(
+                "The submission deadline of the RFP {} has passed, so you can't link to this RFP",
+                rfp_id
+            )
+        );
+    }
+
+    fn was_proposal_approved(&self, proposal_id: ProposalId) -> bool {
+        self.proposals.get(proposal_id.into()).map_or(false, |proposal| {
+            Into::<Proposal>::into(proposal)
+                .snapshot
+                .body
+                .latest_version()
+                .timeline
+                .latest_version()
+                .was_approved()
+        })
+    }
+
     fn get_rfp_labels(&self, rfp_id: RFPId) -> HashSet<String> {
         let rfp: RFP = self
             .rfps
@@ -153,18 +338,43 @@ impl Contract {
         rfp.snapshot.labels
     }
 
+    /// A proposal listed in the selected proposals of the RFP must stay linked and approved, it
+    /// has to be removed from the selected proposals first.
+    pub(crate) fn assert_proposal_not_selected_in_rfp(
+        &self,
+        rfp_id: RFPId,
+        proposal_id: ProposalId,
+    ) {
+        let rfp = self.load_rfp(rfp_id);
+        require!(
+            rfp.snapshot
+                .body
+                .latest_version()
+                .selected_proposals
+                .iter()
+                .all(|selected| selected.proposal_id != proposal_id),
+            format!(
+                "Proposal {} is selected in the RFP {}, remove it from the selected proposals first",
+                proposal_id, rfp_id
+            )
+        );
+    }
+
     pub(crate) fn get_linked_proposals_in_rfp(&self, rfp_id: RFPId) -> HashSet<ProposalId> {
-        let rfp: RFP = self.get_rfp(rfp_id).into();
+        let rfp: RFP = self.load_rfp(rfp_id);
         rfp.snapshot.linked_proposals
     }
 
+    /// Updates the linked proposals of the current snapshot in place, as storing a whole snapshot
+    /// for every link would make the history grow with each proposal. The snapshots of the
+    /// linked proposals keep the history of the links.
     fn change_linked_proposal_in_rfp(
         &mut self,
         rfp_id: RFPId,
         proposal_id: ProposalId,
         operation: LinkedProposalChangeOperation,
     ) {
-        let mut rfp: RFP = self.get_rfp(rfp_id).into();
+        let mut rfp: RFP = self.load_rfp(rfp_id);
         let mut linked_proposals = rfp.snapshot.linked_proposals.clone();
         match operation {
             LinkedProposalChangeOperation::Add => {
@@ -174,17 +384,8 @@ impl Contract {
                 linked_proposals.remove(&proposal_id);
             }
         }
-        rfp.snapshot_history.push(rfp.snapshot.block_height);
-        let new_snapshot = RFPSnapshot {
-            editor_id: env::predecessor_account_id(),
-            timestamp: env::block_timestamp(),
-            block_height: env::block_height(),
-            labels: rfp.snapshot.labels,
-            body: rfp.snapshot.body,
-            linked_proposals,
-        };
-        rfp.snapshot = new_snapshot;
-        self.rfps.replace(rfp_id.into(), &rfp.clone().into());
+        rfp.snapshot.linked_proposals = linked_proposals;
+        self.rfps.replace(rfp_id.into(), &rfp.into());
     }
 
     fn add_linked_proposal_in_rfp(&mut self, rfp_id: RFPId, proposal_id: ProposalId) {
@@ -214,9 +415,11 @@ impl Contract {
             self.assert_can_link_unlink_rfp(new_body.linked_rfp);
             self.assert_can_link_unlink_rfp(old_rfp_id);
             if let Some(old_rfp_id) = old_rfp_id {
+                self.assert_proposal_not_selected_in_rfp(old_rfp_id, proposal_id);
                 self.remove_linked_proposal_in_rfp(old_rfp_id, proposal_id);
             }
             if let Some(new_rfp_id) = new_body.linked_rfp {
+                self.assert_rfp_submission_deadline_not_passed(new_rfp_id);
                 self.add_linked_proposal_in_rfp(new_rfp_id, proposal_id);
             }
         }
@@ -237,32 +440,67 @@ impl Contract {
             self.is_allowed_to_write_rfps(editor_id.clone()),
             "The account is not allowed to edit RFPs"
         );
+        self.edit_rfp_unchecked(id, body, labels)
+    }
 
-        let mut rfp: RFP = self.get_rfp(id).into();
+    /// Edits the RFP without checking whether the account can write RFPs.
+    pub(crate) fn edit_rfp_unchecked(
+        &mut self,
+        id: RFPId,
+        body: VersionedRFPBody,
+        labels: HashSet<String>,
+    ) -> RFPId {
+        let mut rfp: RFP = self.load_rfp(id);
 
         let rfp_body = body.clone().latest_version();
 
+        require!(
+            rfp_body.selected_proposals.is_empty() || rfp_body.timeline.is_proposal_selected(),
+            "Only an RFP in the Proposal Selected state can have selected proposals"
+        );
+        require!(
+            rfp_body.selected_proposals.is_empty() || rfp_body.total_budget_usd_amount.is_some(),
+            "The RFP needs a budget before proposals can be selected"
+        );
+        require!(
+            rfp_body.total_allocated_usd_amount()
+                <= rfp_body.total_budget_usd_amount.unwrap_or(0) as u64,
+            "The amounts allocated to the selected proposals exceed the RFP budget"
+        );
+        let linked_proposals = self.get_linked_proposals_in_rfp(id);
+        let mut selected_proposals = HashSet::new();
+        for selected in &rfp_body.selected_proposals {
+            let proposal_id = selected.proposal_id;
+            require!(
+                selected_proposals.insert(proposal_id),
+                format!("Proposal {} is selected more than once", proposal_id)
+            );
+            require!(
+                linked_proposals.contains(&proposal_id),
+                format!("Proposal {} is not linked to the RFP {}", proposal_id, id)
+            );
+            require!(
+                self.was_proposal_approved(proposal_id),
+                format!("Proposal {} can't be selected because it was not approved", proposal_id)
+            );
+        }
+
         if rfp_body.timeline.is_proposal_selected() {
-            let has_approved_proposal = self
-                .get_rfp_linked_proposals(id)
-                .into_iter()
-                .filter_map(|proposal_id| self.proposals.get(proposal_id.into()))
-                .any(|proposal| {
-                    Into::<Proposal>::into(proposal)
-                        .snapshot
-                        .body
-                        .latest_version()
-                        .timeline
-                        .latest_version()
-                        .was_approved()
-                });
-            require!(has_approved_proposal, "Cannot change RFP status to Proposal Selected without an approved proposal linked to this RFP");
+            // RFPs that do not list the winners count every approved linked proposal as selected.
+            if selected_proposals.is_empty() {
+                selected_proposals = linked_proposals
+                    .into_iter()
+                    .filter(|proposal_id| self.was_proposal_approved(*proposal_id))
+                    .collect();
+            }
+            require!(!selected_proposals.is_empty(), "Cannot change RFP status to Proposal Selected without an approved proposal linked to this RFP");
+            self.assert_rfp_selection_follows_ranking(id, &selected_proposals);
         }
 
         let old_snapshot = rfp.snapshot.clone();
         let old_labels_set = old_snapshot.labels.clone();
         let new_labels = labels;
-        rfp.snapshot_history.push(rfp.snapshot.block_height);
+        rfp.snapshot_history.push(old_snapshot.clone());
         let new_snapshot = RFPSnapshot {
             editor_id: env::predecessor_account_id(),
             timestamp: env::block_timestamp(),
@@ -285,6 +523,8 @@ impl Contract {
 
         let labels_to_remove = &old_labels_set - &new_labels_set;
         let labels_to_add: HashSet<String> = &new_labels_set - &old_labels_set;
+        assert_labels_not_rules(&labels_to_add);
+        self.assert_labels_not_deprecated(&labels_to_add);
         for label_to_remove in labels_to_remove {
             let mut rfps = self.label_to_rfps.get(&label_to_remove).unwrap();
             rfps.remove(&id);
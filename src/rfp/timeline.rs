@@ -15,6 +15,10 @@ impl TimelineStatus {
         matches!(self, TimelineStatus::AcceptingSubmissions)
     }
 
+    pub fn is_evaluation(&self) -> bool {
+        matches!(self, TimelineStatus::Evaluation)
+    }
+
     pub fn is_cancelled(&self) -> bool {
         matches!(self, TimelineStatus::Cancelled)
     }
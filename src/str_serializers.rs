@@ -54,3 +54,27 @@ pub mod u32_dec_format {
         String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
     }
 }
+
+pub mod option_u32_dec_format {
+    use near_sdk::serde::de;
+    use near_sdk::serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(num: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match num {
+            Some(num) => serializer.serialize_some(&num.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|num| num.parse().map_err(de::Error::custom))
+            .transpose()
+    }
+}
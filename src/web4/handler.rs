@@ -114,10 +114,16 @@ pub fn web4_get(contract: &Contract, request: Web4Request) -> Web4Response {
 
     match (page, path_parts.get(2)) {
         ("community", Some(handle)) => {
-            if let Some(community) = contract.get_community(handle.to_string()) {
+            // Old handles of renamed communities redirect to the new handle.
+            let mut handle = handle.to_string();
+            if let Some(community) = contract
+                .get_community(handle.clone())
+                .filter(|community| !contract.is_community_archived(community.handle.clone()))
+            {
                 title = format!(" - Community - {}", community.name);
                 description = community.description;
                 image = community.logo_url;
+                handle = community.handle;
             } else {
                 title = format!(" - Community - {}", handle);
             }
@@ -443,6 +449,47 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_renamed_community_path() {
+        let context = VMContextBuilder::new()
+            .current_account_id("not-only-devhub.near".parse().unwrap())
+            .attached_deposit(NearToken::from_near(4))
+            .build();
+        testing_env!(context);
+        let mut contract = Contract::new();
+        contract.create_community(CommunityInputs {
+            handle: String::from("webassemblymusic"),
+            name: String::from("WebAssembly Music"),
+            description: String::from("Music stored forever in the NEAR blockchain"),
+            tag: String::from("wasm"),
+            logo_url: String::from("https://example.com/logo.png"),
+            banner_url: String::from("https://example.com/banner.png"),
+            bio_markdown: None,
+        });
+        contract.rename_community(String::from("webassemblymusic"), String::from("wasmmusic"));
+
+        let response = web4_get(
+            &contract,
+            serde_json::from_value(serde_json::json!({
+                "path": "/community/webassemblymusic",
+                "preloads": create_preload_result(String::from("title"), String::from("description")),
+            }))
+            .unwrap(),
+        );
+        match response {
+            Web4Response::Body { body, .. } => {
+                let body_string = String::from_utf8(BASE64_STANDARD.decode(body).unwrap()).unwrap();
+                assert!(body_string.contains("https://near.social/not-only-devhub.near/widget/app?page=community&handle=wasmmusic"));
+                let expected_initial_props_string =
+                    json!({"page": "community", "handle": "wasmmusic"}).to_string();
+                assert!(body_string.contains(&expected_initial_props_string));
+            }
+            _ => {
+                panic!("Should return Web4Response::Body");
+            }
+        }
+    }
+
     #[test]
     pub fn test_web4_unknown_path() {
         view_test_env();
@@ -796,6 +843,7 @@ mod tests {
                 body: VersionedRFPBody::V0(rfp_body),
             },
             snapshot_history: vec![],
+            legacy_snapshot_history: vec![],
         };
 
         contract.rfps.push(&rfp.clone().into());
@@ -45,7 +45,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "description": "some description",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test1", "test2"],
         }))
@@ -80,7 +80,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "category": "Events",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test1", "test2"],
         }))
@@ -110,7 +110,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "category": "Events",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test3"],
         }))
@@ -158,7 +158,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "category": "Events",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test2", "test3"],
         }))
@@ -221,7 +221,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "description": "some description",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test4"],
         }))
@@ -312,7 +312,7 @@ async fn test_rfp() -> anyhow::Result<()> {
                 "description": "some description",
                 "summary": "sum",
                 "timeline": {"status": "ACCEPTING_SUBMISSIONS"},
-                "submission_deadline": "1707821848175250170"
+                "submission_deadline": "4102444800000000000"
             },
             "labels": ["test2", "test3"],
         }))